@@ -0,0 +1,18 @@
+// An application, as an item of the apps list model
+use crate::desktop_entries::AppEntry;
+use gtk::glib;
+use gtk::subclass::prelude::*;
+use std::cell::OnceCell;
+
+#[derive(Default)]
+pub struct AppObject {
+    pub entry: OnceCell<AppEntry>,
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for AppObject {
+    const NAME: &'static str = "AppObject";
+    type Type = super::AppObject;
+}
+
+impl ObjectImpl for AppObject {}
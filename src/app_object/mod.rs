@@ -0,0 +1,28 @@
+mod imp;
+
+use crate::desktop_entries::AppEntry;
+use glib::Object;
+use gtk::glib;
+use gtk::subclass::prelude::*;
+
+glib::wrapper! {
+    pub struct AppObject(ObjectSubclass<imp::AppObject>);
+}
+
+impl AppObject {
+    pub fn new(entry: AppEntry) -> Self {
+        let obj: Self = Object::builder().build();
+        obj.imp()
+            .entry
+            .set(entry)
+            .expect("The entry is only set once");
+        obj
+    }
+
+    pub fn entry(&self) -> &AppEntry {
+        self.imp()
+            .entry
+            .get()
+            .expect("AppObject created without an entry")
+    }
+}
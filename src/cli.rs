@@ -0,0 +1,418 @@
+// Headless command-line interface, usable without a display
+use crate::desktop_entries::{DesktopEntryManager, is_valid_desktop_file_id};
+use crate::mime_database::is_valid_mimetype;
+use crate::mimetype_manager::MimetypeManager;
+use std::fmt;
+
+const COMMANDS: &[&str] = &[
+    "get",
+    "set",
+    "unset",
+    "list",
+    "query-apps-for",
+    "which-file",
+    "help",
+    "--help",
+];
+
+const USAGE: &str = "\
+Usage: pick_def_app [COMMAND] [--json]
+       pick_def_app [FILE]...
+
+Without a command, the graphical interface is started, showing what opens
+each FILE given.
+
+Commands:
+  get <mimetype>...                 Show the effective default of each type
+  set <desktop-file> <mimetype>...  Make an application the preferred default
+  unset <mimetype>...               Remove the user's defaults for each type
+  list                              List every type with a default
+  query-apps-for <mimetype>         List the applications able to open a type
+  which-file <mimetype>             Show which mimeapps.list sets the default
+  help                              Show this help
+
+Options:
+  --json                            Print machine-readable JSON";
+
+/// Whether `args` (without the program name) are a command to run headless
+/// rather than files to open. `--json` can come anywhere, even first.
+pub fn is_command_line(args: &[String]) -> bool {
+    let (json, args) = split_options(args);
+    json || args.first().is_some_and(|arg| COMMANDS.contains(arg))
+}
+
+// Whether `--json` was given, and the other arguments
+fn split_options(args: &[String]) -> (bool, Vec<&str>) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let args = args
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| *arg != "--json")
+        .collect();
+    (json, args)
+}
+
+/// Runs `args` (without the program name) and returns the exit status.
+pub fn run(args: &[String]) -> u8 {
+    let (json, args) = split_options(args);
+
+    let Some((command, operands)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let result = match *command {
+        "help" | "--help" => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        "get" if !operands.is_empty() => get(operands),
+        "set" if operands.len() >= 2 => set(operands[0], &operands[1..]),
+        "unset" if !operands.is_empty() => unset(operands),
+        "list" if operands.is_empty() => list(),
+        "query-apps-for" if operands.len() == 1 => query_apps_for(operands[0]),
+        "which-file" if operands.len() == 1 => which_file(operands[0]),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    match result {
+        Ok(output) => {
+            if json {
+                println!("{}", output.json);
+            } else if !output.text.is_empty() {
+                println!("{}", output.text.trim_end());
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+// What a command prints, in both formats
+struct Output {
+    text: String,
+    json: Json,
+}
+
+type CommandResult = Result<Output, Box<dyn std::error::Error>>;
+
+fn load_desktop_manager() -> DesktopEntryManager {
+    let mut desktop_manager = DesktopEntryManager::new();
+    if let Err(e) = desktop_manager.load_entries() {
+        eprintln!("Failed to load desktop entries: {}", e);
+    }
+    desktop_manager
+}
+
+// The effective default of `mimetype`, as JSON and as a line of text
+fn describe_default(
+    mimetype_manager: &MimetypeManager,
+    desktop_manager: &DesktopEntryManager,
+    mimetype: &str,
+) -> (Json, String) {
+    let default = mimetype_manager
+        .get_effective_default(mimetype, |app| desktop_manager.has_desktop_file(app));
+
+    let json = Json::object([
+        ("mimetype", Json::from(mimetype)),
+        (
+            "default",
+            default
+                .as_ref()
+                .map_or(Json::Null, |d| Json::from(d.desktop_file.as_str())),
+        ),
+        (
+            "source",
+            default.as_ref().map_or(Json::Null, |d| {
+                Json::from(d.source.display().to_string().as_str())
+            }),
+        ),
+        (
+            "user_defaults",
+            Json::strings(mimetype_manager.get_default_apps(mimetype)),
+        ),
+    ]);
+
+    let text = match &default {
+        Some(default) => format!(
+            "{}\t{}\t{}",
+            mimetype,
+            default.desktop_file,
+            default.source.display()
+        ),
+        None => format!("{}\t-", mimetype),
+    };
+
+    (json, text)
+}
+
+// One line or object per type, describing its effective default
+fn defaults_output(
+    mimetype_manager: &MimetypeManager,
+    desktop_manager: &DesktopEntryManager,
+    mimetypes: &[impl AsRef<str>],
+) -> Output {
+    let (json, text): (Vec<Json>, Vec<String>) = mimetypes
+        .iter()
+        .map(|mimetype| describe_default(mimetype_manager, desktop_manager, mimetype.as_ref()))
+        .unzip();
+
+    Output {
+        text: text.join("\n"),
+        json: Json::Array(json),
+    }
+}
+
+fn get(mimetypes: &[&str]) -> CommandResult {
+    let mimetype_manager = MimetypeManager::new()?;
+    let desktop_manager = load_desktop_manager();
+
+    Ok(defaults_output(
+        &mimetype_manager,
+        &desktop_manager,
+        mimetypes,
+    ))
+}
+
+// Refuses arguments that would corrupt mimeapps.list, like `a=b` or
+// `[Removed Associations]`, before anything is changed
+fn check_mimetypes(mimetypes: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    match mimetypes
+        .iter()
+        .find(|mimetype| !is_valid_mimetype(mimetype))
+    {
+        Some(mimetype) => Err(format!("{:?} is not a valid MIME type", mimetype).into()),
+        None => Ok(()),
+    }
+}
+
+fn set(desktop_file: &str, mimetypes: &[&str]) -> CommandResult {
+    if !is_valid_desktop_file_id(desktop_file) {
+        return Err(format!(
+            "{:?} is not a valid desktop file ID (like org.gnome.TextEditor.desktop)",
+            desktop_file
+        )
+        .into());
+    }
+    check_mimetypes(mimetypes)?;
+
+    let mut mimetype_manager = MimetypeManager::new()?;
+    let desktop_manager = load_desktop_manager();
+
+    if !desktop_manager.has_desktop_file(desktop_file) {
+        eprintln!("Warning: {} is not installed", desktop_file);
+    }
+
+    let description = format!(
+        "Set {} as default for {}",
+        desktop_file,
+        mimetypes.join(", ")
+    );
+    mimetype_manager.record(&description, |mimetype_manager| {
+        for mimetype in mimetypes {
+            mimetype_manager.set_default_app(mimetype, desktop_file);
+        }
+    });
+    mimetype_manager.save()?;
+
+    Ok(defaults_output(
+        &mimetype_manager,
+        &desktop_manager,
+        mimetypes,
+    ))
+}
+
+fn unset(mimetypes: &[&str]) -> CommandResult {
+    check_mimetypes(mimetypes)?;
+
+    let mut mimetype_manager = MimetypeManager::new()?;
+    let desktop_manager = load_desktop_manager();
+
+    let description = format!("Unset the defaults of {}", mimetypes.join(", "));
+    mimetype_manager.record(&description, |mimetype_manager| {
+        for mimetype in mimetypes {
+            mimetype_manager.unset_default_apps(mimetype);
+        }
+    });
+    mimetype_manager.save()?;
+
+    // Another mimeapps.list may still provide a default
+    Ok(defaults_output(
+        &mimetype_manager,
+        &desktop_manager,
+        mimetypes,
+    ))
+}
+
+fn list() -> CommandResult {
+    let mimetype_manager = MimetypeManager::new()?;
+    let desktop_manager = load_desktop_manager();
+
+    Ok(defaults_output(
+        &mimetype_manager,
+        &desktop_manager,
+        &mimetype_manager.get_all_mimetypes(),
+    ))
+}
+
+fn query_apps_for(mimetype: &str) -> CommandResult {
+    let mimetype_manager = MimetypeManager::new()?;
+    let desktop_manager = load_desktop_manager();
+
+    let declared = desktop_manager
+        .get_entries_for_mimetype(mimetype)
+        .into_iter()
+        .map(|entry| entry.id.clone());
+    let apps = mimetype_manager.get_associated_apps(mimetype, declared);
+
+    let mut json = Vec::new();
+    let mut text = Vec::new();
+    for app in apps {
+        let entry = desktop_manager.get_entry_by_desktop_file(&app);
+        let name = entry.map(|entry| entry.name.as_str());
+        let path = entry.map(|entry| entry.path.display().to_string());
+
+        json.push(Json::object([
+            ("desktop_file", Json::from(app.as_str())),
+            ("name", name.map_or(Json::Null, Json::from)),
+            ("path", path.as_deref().map_or(Json::Null, Json::from)),
+            ("installed", Json::Bool(entry.is_some())),
+        ]));
+        text.push(format!("{}\t{}", app, name.unwrap_or("(not installed)")));
+    }
+
+    Ok(Output {
+        text: text.join("\n"),
+        json: Json::Array(json),
+    })
+}
+
+fn which_file(mimetype: &str) -> CommandResult {
+    let mimetype_manager = MimetypeManager::new()?;
+    let desktop_manager = load_desktop_manager();
+
+    let default = mimetype_manager
+        .get_effective_default(mimetype, |app| desktop_manager.has_desktop_file(app));
+    let source = default.map(|default| default.source.display().to_string());
+
+    Ok(Output {
+        text: source.clone().unwrap_or_else(|| "-".to_string()),
+        json: Json::object([
+            ("mimetype", Json::from(mimetype)),
+            ("file", source.as_deref().map_or(Json::Null, Json::from)),
+        ]),
+    })
+}
+
+// Just enough JSON to print command results
+enum Json {
+    Null,
+    Bool(bool),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn object<const N: usize>(fields: [(&str, Json); N]) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    fn strings(items: &[String]) -> Self {
+        Json::Array(items.iter().map(|item| Json::from(item.as_str())).collect())
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::String(value) => write_json_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn commands_are_found_after_options() {
+        assert!(is_command_line(&args(&["get", "text/plain"])));
+        assert!(is_command_line(&args(&["--json", "get", "text/plain"])));
+        assert!(is_command_line(&args(&["--json"])));
+        assert!(!is_command_line(&args(&["report.pdf"])));
+        assert!(!is_command_line(&args(&[])));
+    }
+
+    #[test]
+    fn invalid_arguments_are_refused() {
+        assert_eq!(run(&args(&["set", "x.desktop", "a=b"])), 1);
+        assert_eq!(
+            run(&args(&["set", "evil.desktop", "[Removed Associations]"])),
+            1
+        );
+        assert_eq!(run(&args(&["set", "a;b.desktop", "text/plain"])), 1);
+        assert_eq!(run(&args(&["set", "firefox", "text/plain"])), 1);
+        assert_eq!(run(&args(&["unset", "text/plain\n[x]"])), 1);
+    }
+}
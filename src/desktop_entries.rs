@@ -1,105 +1,325 @@
+use crate::xdg_dirs;
 use freedesktop_desktop_entry::*;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Why an application is left out of the applications list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HiddenReason {
+    // `Hidden=true`: the entry counts as deleted
+    Hidden,
+    // `NoDisplay=true`: kept out of menus, but still opens files
+    NoDisplay,
+    // Desktops from `OnlyShowIn`, none of them the current one. Like
+    // `NotShowIn`, it only keeps the entry out of menus.
+    OnlyShownIn(Vec<String>),
+    // The current desktop, listed in `NotShowIn`
+    NotShownIn(String),
+    // The `TryExec` program, which isn't installed
+    TryExecMissing(String),
+}
+
+impl fmt::Display for HiddenReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HiddenReason::Hidden => write!(f, "Hidden"),
+            HiddenReason::NoDisplay => write!(f, "Not in menus"),
+            HiddenReason::OnlyShownIn(desktops) => write!(f, "Only in {}", desktops.join(", ")),
+            HiddenReason::NotShownIn(desktop) => write!(f, "Not in {}", desktop),
+            HiddenReason::TryExecMissing(program) => write!(f, "{} not found", program),
+        }
+    }
+}
+
+/// A main category of the Desktop Menu specification, as listed in the
+/// sidebar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MainCategory {
+    pub id: &'static str,
+    pub label: &'static str,
+}
+
+/// The main categories, in the order they're listed. Audio and Video are
+/// part of AudioVideo, and `Other` collects applications without any known
+/// category.
+pub const MAIN_CATEGORIES: &[MainCategory] = &[
+    MainCategory {
+        id: "AudioVideo",
+        label: "Multimedia",
+    },
+    MainCategory {
+        id: "Development",
+        label: "Development",
+    },
+    MainCategory {
+        id: "Education",
+        label: "Education",
+    },
+    MainCategory {
+        id: "Game",
+        label: "Games",
+    },
+    MainCategory {
+        id: "Graphics",
+        label: "Graphics",
+    },
+    MainCategory {
+        id: "Network",
+        label: "Internet",
+    },
+    MainCategory {
+        id: "Office",
+        label: "Office",
+    },
+    MainCategory {
+        id: "Science",
+        label: "Science",
+    },
+    MainCategory {
+        id: "Settings",
+        label: "Settings",
+    },
+    MainCategory {
+        id: "System",
+        label: "System",
+    },
+    MainCategory {
+        id: "Utility",
+        label: "Accessories",
+    },
+    MainCategory {
+        id: "Other",
+        label: "Other",
+    },
+];
+
+/// Where an application was installed from, told by its desktop file's path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppSource {
+    // A system package, or installed by hand under `$XDG_DATA_DIRS`
+    System,
+    // Under `$XDG_DATA_HOME`
+    User,
+    Flatpak,
+    Snap,
+    // A Nix profile or the NixOS system
+    Nix,
+}
+
+impl fmt::Display for AppSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppSource::System => write!(f, "System"),
+            AppSource::User => write!(f, "User"),
+            AppSource::Flatpak => write!(f, "Flatpak"),
+            AppSource::Snap => write!(f, "Snap"),
+            AppSource::Nix => write!(f, "Nix"),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AppEntry {
+    // Desktop file ID (e.g. `firefox.desktop` or `kde4-okular.desktop`), as
+    // used in `mimeapps.list`
+    pub id: String,
+    // Name, GenericName, Comment and Keywords are in the user's language
+    // when the desktop file is translated
     pub name: String,
+    pub generic_name: Option<String>,
+    pub comment: Option<String>,
+    pub keywords: Vec<String>,
     pub icon: Option<String>,
     pub exec: String,
     pub mimetypes: Vec<String>,
     pub categories: Vec<String>,
     pub path: PathBuf,
+    pub hidden_reasons: Vec<HiddenReason>,
+}
+
+impl AppEntry {
+    /// Whether the application can be used on this system, so it can open
+    /// files. Only `Hidden` and a missing `TryExec` program rule it out.
+    pub fn is_available(&self) -> bool {
+        !self.hidden_reasons.iter().any(|reason| {
+            matches!(
+                reason,
+                HiddenReason::Hidden | HiddenReason::TryExecMissing(_)
+            )
+        })
+    }
+
+    /// Whether the application belongs in the applications list.
+    pub fn is_listed(&self) -> bool {
+        self.hidden_reasons.is_empty()
+    }
+
+    /// IDs of the main categories the application is listed under. Additional
+    /// categories only count for applications declaring no main category, as
+    /// the one they're related to.
+    pub fn main_categories(&self) -> Vec<&'static str> {
+        let mut main_categories: Vec<&'static str> = self
+            .categories
+            .iter()
+            .filter_map(|category| main_category(category))
+            .collect();
+        if main_categories.is_empty() {
+            main_categories = self
+                .categories
+                .iter()
+                .filter_map(|category| related_main_category(category))
+                .collect();
+        }
+        if main_categories.is_empty() {
+            main_categories.push("Other");
+        }
+
+        main_categories.sort_unstable();
+        main_categories.dedup();
+        main_categories
+    }
+
+    pub fn source(&self) -> AppSource {
+        let path = self.path.to_string_lossy();
+
+        // Flatpak exports can be under the user's data directory too
+        if path.contains("/flatpak/exports/") {
+            AppSource::Flatpak
+        } else if path.starts_with("/var/lib/snapd/") || path.starts_with("/snap/") {
+            AppSource::Snap
+        } else if path.starts_with("/nix/")
+            || path.starts_with("/run/current-system/")
+            || path.starts_with("/etc/profiles/")
+            || path.contains("/.nix-profile/")
+            || path.contains("/.local/state/nix/profiles/")
+        {
+            AppSource::Nix
+        } else if xdg_dirs::data_home().is_some_and(|data_home| self.path.starts_with(data_home)) {
+            AppSource::User
+        } else {
+            AppSource::System
+        }
+    }
+}
+
+/// A desktop file ignored because another file with the same ID takes
+/// precedence.
+#[derive(Debug, Clone)]
+pub struct ShadowedEntry {
+    pub id: String,
+    pub path: PathBuf,
+    pub shadowed_by: PathBuf,
 }
 
 pub struct DesktopEntryManager {
     entries: HashMap<String, AppEntry>,
+    shadowed: Vec<ShadowedEntry>,
 }
 
 impl DesktopEntryManager {
     pub fn new() -> Self {
         Self {
             entries: HashMap::new(),
+            shadowed: Vec::new(),
         }
     }
 
     pub fn load_entries(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.load_entries_from(&applications_dirs());
+        Ok(())
+    }
+
+    // Loads the desktop files under `dirs`, the highest precedence first
+    fn load_entries_from(&mut self, dirs: &[PathBuf]) {
         let locales = get_languages_from_env();
+        self.entries.clear();
+        self.shadowed.clear();
+
+        // The first file with a given ID wins, even when it can't be used
+        let mut seen: HashMap<String, PathBuf> = HashMap::new();
+
+        // Walk each applications directory on its own, as desktop file IDs
+        // are relative to the directory they were found in
+        for dir in dirs {
+            for path in Iter::new(std::iter::once(dir.clone())) {
+                let id = desktop_file_id(dir, &path);
+                if let Some(shadowed_by) = seen.get(&id) {
+                    self.shadowed.push(ShadowedEntry {
+                        id,
+                        path,
+                        shadowed_by: shadowed_by.clone(),
+                    });
+                    continue;
+                }
+                seen.insert(id.clone(), path.clone());
 
-        let entries = Iter::new(default_paths())
-            .entries(Some(&locales))
-            .collect::<Vec<_>>();
-
-        // Use HashMap to automatically handle duplicates - last entry wins
-        for entry in entries {
-            if let Some(app_entry) = self.parse_entry(entry) {
-                // Use the desktop file name (without .desktop extension) as key
-                let key = app_entry
-                    .path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or(&app_entry.name)
-                    .to_string();
-
-                // Temporarily, skip apps with no associated mimetypes, maybe in a future customise which app can open what?
-                if app_entry.mimetypes.is_empty() {
+                let Ok(entry) = DesktopEntry::from_path(path, Some(&locales)) else {
                     continue;
-                } else {
-                    self.entries.insert(key, app_entry);
+                };
+                // Apps without mimetypes are kept too, they can be associated
+                // with any type
+                if let Some(app_entry) = self.parse_entry(id, entry, &locales) {
+                    self.entries.insert(app_entry.id.clone(), app_entry);
                 }
             }
         }
+    }
 
-        Ok(())
+    // Entries that can be used, the only ones offered to open files
+    fn available(&self) -> impl Iterator<Item = &AppEntry> {
+        self.entries.values().filter(|entry| entry.is_available())
     }
 
     pub fn get_entries(&self) -> Vec<&AppEntry> {
+        self.available().collect()
+    }
+
+    /// Every entry, including hidden and unavailable applications.
+    pub fn get_all_entries(&self) -> Vec<&AppEntry> {
         self.entries.values().collect()
     }
 
-    pub fn get_entries_for_mimetype(&self, mimetype: &str) -> Vec<&AppEntry> {
-        self.entries
-            .values()
-            .filter(|entry| entry.mimetypes.contains(&mimetype.to_string()))
-            .collect()
+    /// Directories desktop files are loaded from, the highest precedence first.
+    pub fn applications_dirs(&self) -> Vec<PathBuf> {
+        applications_dirs()
+    }
+
+    /// Desktop files hidden by another file with the same ID.
+    pub fn get_shadowed_entries(&self) -> &[ShadowedEntry] {
+        &self.shadowed
     }
 
-    pub fn search_entries(&self, query: &str) -> Vec<&AppEntry> {
-        let query = query.to_lowercase();
+    /// The application with this desktop file ID (e.g. `firefox.desktop`).
+    pub fn get_entry_by_desktop_file(&self, desktop_file: &str) -> Option<&AppEntry> {
         self.entries
-            .values()
-            .filter(|entry| {
-                entry.name.to_lowercase().contains(&query)
-                    || entry
-                        .categories
-                        .iter()
-                        .any(|cat| cat.to_lowercase().contains(&query))
-            })
-            .collect()
+            .get(desktop_file)
+            .filter(|entry| entry.is_available())
     }
 
-    pub fn get_all_categories(&self) -> Vec<String> {
-        use std::collections::HashSet;
+    /// Like [`Self::get_entry_by_desktop_file`], but also finds hidden and
+    /// unavailable applications.
+    pub fn get_any_entry(&self, desktop_file: &str) -> Option<&AppEntry> {
+        self.entries.get(desktop_file)
+    }
 
-        let mut categories = HashSet::new();
-        for entry in self.entries.values() {
-            for category in &entry.categories {
-                if !category.is_empty() {
-                    categories.insert(category.clone());
-                }
-            }
-        }
+    /// Whether an application with this desktop file ID (e.g. `firefox.desktop`) is installed.
+    pub fn has_desktop_file(&self, desktop_file: &str) -> bool {
+        self.get_entry_by_desktop_file(desktop_file).is_some()
+    }
 
-        let mut sorted_categories: Vec<String> = categories.into_iter().collect();
-        sorted_categories.sort();
-        sorted_categories
+    pub fn get_entries_for_mimetype(&self, mimetype: &str) -> Vec<&AppEntry> {
+        self.available()
+            .filter(|entry| entry.mimetypes.contains(&mimetype.to_string()))
+            .collect()
     }
 
     pub fn get_all_mimetypes(&self) -> Vec<String> {
         use std::collections::HashSet;
 
         let mut mimetypes = HashSet::new();
-        for entry in self.entries.values() {
+        for entry in self.available() {
             for mimetype in &entry.mimetypes {
                 if !mimetype.is_empty() {
                     mimetypes.insert(mimetype.clone());
@@ -116,7 +336,7 @@ impl DesktopEntryManager {
         use std::collections::HashSet;
 
         let mut main_types = HashSet::new();
-        for entry in self.entries.values() {
+        for entry in self.available() {
             for mimetype in &entry.mimetypes {
                 if !mimetype.is_empty() {
                     // Extract the main type (part before the slash)
@@ -133,11 +353,33 @@ impl DesktopEntryManager {
         sorted_main_types.sort();
         sorted_main_types
     }
-    fn parse_entry(&self, entry: DesktopEntry) -> Option<AppEntry> {
-        let empty_locales: &[String] = &[];
-        let name = entry.name(empty_locales)?.to_string();
+    fn parse_entry(&self, id: String, entry: DesktopEntry, locales: &[String]) -> Option<AppEntry> {
+        let name = entry.name(locales)?.to_string();
+        let generic_name = entry
+            .generic_name(locales)
+            .map(|name| name.to_string())
+            .filter(|name| !name.is_empty());
+        let comment = entry
+            .comment(locales)
+            .map(|comment| comment.to_string())
+            .filter(|comment| !comment.is_empty());
+        let keywords = entry
+            .keywords(locales)
+            .map(|keywords| {
+                keywords
+                    .iter()
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(|keyword| keyword.to_string())
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
         let icon = entry.icon().map(|s| s.to_string());
-        let exec = entry.exec()?.to_string();
+        // Deleted entries often only carry `Hidden=true`
+        let exec = match entry.exec() {
+            Some(exec) => exec.to_string(),
+            None if entry.hidden() => String::new(),
+            None => return None,
+        };
 
         let mimetypes = entry
             .mime_type()
@@ -150,17 +392,337 @@ impl DesktopEntryManager {
             .unwrap_or_default();
 
         Some(AppEntry {
+            id,
             name,
+            generic_name,
+            comment,
+            keywords,
             icon,
             exec,
             mimetypes,
             categories,
             path: entry.path.clone(),
+            hidden_reasons: hidden_reasons(&entry, &xdg_dirs::current_desktops()),
         })
     }
 }
+
+// The main category `category` is, or is part of
+fn main_category(category: &str) -> Option<&'static str> {
+    match category {
+        "AudioVideo" | "Audio" | "Video" => Some("AudioVideo"),
+        _ => MAIN_CATEGORIES
+            .iter()
+            .find(|main_category| main_category.id == category && category != "Other")
+            .map(|main_category| main_category.id),
+    }
+}
+
+// The main category an additional category goes with, following the table
+// of the Desktop Menu specification. When several are allowed, the one
+// users would look in first.
+fn related_main_category(category: &str) -> Option<&'static str> {
+    let main_category = match category {
+        "Building" | "Debugger" | "IDE" | "GUIDesigner" | "Profiling" | "RevisionControl"
+        | "Translation" | "WebDevelopment" => "Development",
+        "Calendar" | "ContactManagement" | "Database" | "Dictionary" | "Chart" | "Finance"
+        | "FlowChart" | "PDA" | "ProjectManagement" | "Presentation" | "Spreadsheet"
+        | "WordProcessor" => "Office",
+        "2DGraphics" | "VectorGraphics" | "RasterGraphics" | "3DGraphics" | "Scanning" | "OCR"
+        | "Photography" | "Publishing" | "Viewer" => "Graphics",
+        "Email" | "Dialup" | "InstantMessaging" | "Chat" | "IRCClient" | "Feed"
+        | "FileTransfer" | "HamRadio" | "News" | "P2P" | "RemoteAccess" | "Telephony"
+        | "VideoConference" | "WebBrowser" => "Network",
+        "Midi" | "Mixer" | "Sequencer" | "Tuner" | "TV" | "AudioVideoEditing" | "Player"
+        | "Recorder" | "DiscBurning" | "Music" => "AudioVideo",
+        "ActionGame" | "AdventureGame" | "ArcadeGame" | "BoardGame" | "BlocksGame" | "CardGame"
+        | "KidsGame" | "LogicGame" | "RolePlaying" | "Shooter" | "Simulation" | "SportsGame"
+        | "StrategyGame" | "Amusement" => "Game",
+        "Art" | "Construction" | "Languages" | "Literature" | "History" | "Humanities"
+        | "Spirituality" | "Sports" | "Geography" => "Education",
+        "ArtificialIntelligence"
+        | "Astronomy"
+        | "Biology"
+        | "Chemistry"
+        | "ComputerScience"
+        | "DataVisualization"
+        | "Economy"
+        | "Electricity"
+        | "Geology"
+        | "Geoscience"
+        | "ImageProcessing"
+        | "Math"
+        | "NumericalAnalysis"
+        | "MedicalSoftware"
+        | "Physics"
+        | "Robotics"
+        | "ParallelComputing"
+        | "Maps" => "Science",
+        "DesktopSettings" | "HardwareSettings" | "Printing" | "PackageManager"
+        | "Accessibility" | "Security" => "Settings",
+        "Emulator" | "FileManager" | "TerminalEmulator" | "Filesystem" | "Monitor"
+        | "TelephonyTools" | "FileTools" => "System",
+        "TextTools" | "TextEditor" | "Calculator" | "Clock" | "Archiving" | "Compression" => {
+            "Utility"
+        }
+        _ => return None,
+    };
+    Some(main_category)
+}
+
+// Evaluates the keys deciding whether an entry is shown, against the
+// current `desktops` (lowercase) and `$PATH`
+fn hidden_reasons(entry: &DesktopEntry, desktops: &[String]) -> Vec<HiddenReason> {
+    let mut reasons = Vec::new();
+
+    if entry.hidden() {
+        reasons.push(HiddenReason::Hidden);
+    }
+    if entry.no_display() {
+        reasons.push(HiddenReason::NoDisplay);
+    }
+
+    if let Some(only_show_in) = entry.only_show_in()
+        && !only_show_in
+            .iter()
+            .any(|desktop| desktops.contains(&desktop.to_lowercase()))
+    {
+        reasons.push(HiddenReason::OnlyShownIn(
+            only_show_in
+                .iter()
+                // Lists end with `;`
+                .filter(|desktop| !desktop.is_empty())
+                .map(|desktop| desktop.to_string())
+                .collect(),
+        ));
+    }
+
+    if let Some(not_show_in) = entry.not_show_in()
+        && let Some(desktop) = not_show_in
+            .iter()
+            .find(|desktop| desktops.contains(&desktop.to_lowercase()))
+    {
+        reasons.push(HiddenReason::NotShownIn(desktop.to_string()));
+    }
+
+    if let Some(try_exec) = entry.try_exec()
+        && !is_executable_installed(try_exec)
+    {
+        reasons.push(HiddenReason::TryExecMissing(try_exec.to_string()));
+    }
+
+    reasons
+}
+
+// Whether `program`, a path or a name looked up in `$PATH`, can be run
+fn is_executable_installed(program: &str) -> bool {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| is_executable(&dir.join(program)))
+    })
+}
+
+/// `applications` directories, from the highest precedence to the lowest:
+/// the user's first, then `$XDG_DATA_DIRS` in order.
+fn applications_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in xdg_dirs::data_home()
+        .into_iter()
+        .chain(xdg_dirs::data_dirs())
+    {
+        let dir = dir.join("applications");
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Whether `id` can be a desktop file ID, safe to write to `mimeapps.list`.
+pub fn is_valid_desktop_file_id(id: &str) -> bool {
+    id.strip_suffix(".desktop")
+        .is_some_and(|name| !name.is_empty())
+        && !id
+            .chars()
+            .any(|c| c.is_control() || matches!(c, ';' | '=' | '[' | ']' | '/'))
+}
+
+/// The desktop file ID of `path`, found under the applications directory
+/// `dir`: its path relative to `dir`, with `/` replaced by `-`.
+fn desktop_file_id(dir: &Path, path: &Path) -> String {
+    // Subdirectories are walked through their canonical path
+    let relative = path
+        .strip_prefix(dir)
+        .map(Path::to_path_buf)
+        .ok()
+        .or_else(|| {
+            let canonical_dir = dir.canonicalize().ok()?;
+            let canonical_parent = path.parent()?.canonicalize().ok()?;
+            let relative_parent = canonical_parent.strip_prefix(canonical_dir).ok()?;
+            Some(relative_parent.join(path.file_name()?))
+        });
+
+    match relative {
+        Some(relative) => relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("-"),
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
 impl Default for DesktopEntryManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // A fresh directory under the system temporary directory
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "mimetype-desktop-entries-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_entry(path: &Path, name: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            path,
+            format!("[Desktop Entry]\nType=Application\nName={name}\nExec={name}\n"),
+        )
+        .unwrap();
+    }
+
+    // The reasons `keys` hide an application on GNOME
+    fn reasons(keys: &str) -> Vec<HiddenReason> {
+        let input = format!("[Desktop Entry]\nType=Application\nName=App\nExec=app\n{keys}");
+        let entry = DesktopEntry::from_str("/tmp/app.desktop", &input, None::<&[&str]>).unwrap();
+        hidden_reasons(&entry, &["gnome".to_string()])
+    }
+
+    fn app(hidden_reasons: Vec<HiddenReason>) -> AppEntry {
+        AppEntry {
+            id: "app.desktop".to_string(),
+            name: "App".to_string(),
+            generic_name: None,
+            comment: None,
+            keywords: Vec::new(),
+            icon: None,
+            exec: "app".to_string(),
+            mimetypes: Vec::new(),
+            categories: Vec::new(),
+            path: PathBuf::from("/tmp/app.desktop"),
+            hidden_reasons,
+        }
+    }
+
+    #[test]
+    fn hidden_reasons_follow_the_keys() {
+        assert_eq!(reasons(""), vec![]);
+        assert_eq!(reasons("Hidden=true\n"), vec![HiddenReason::Hidden]);
+        assert_eq!(reasons("NoDisplay=true\n"), vec![HiddenReason::NoDisplay]);
+        assert_eq!(
+            reasons("OnlyShowIn=KDE;XFCE;\n"),
+            vec![HiddenReason::OnlyShownIn(vec![
+                "KDE".to_string(),
+                "XFCE".to_string()
+            ])]
+        );
+        assert_eq!(reasons("OnlyShowIn=GNOME;\n"), vec![]);
+        assert_eq!(
+            reasons("NotShowIn=KDE;GNOME;\n"),
+            vec![HiddenReason::NotShownIn("GNOME".to_string())]
+        );
+        assert_eq!(reasons("NotShowIn=KDE;\n"), vec![]);
+        assert_eq!(
+            reasons("TryExec=/nonexistent/app\n"),
+            vec![HiddenReason::TryExecMissing("/nonexistent/app".to_string())]
+        );
+        assert_eq!(reasons("TryExec=/bin/sh\n"), vec![]);
+    }
+
+    #[test]
+    fn only_hidden_and_try_exec_make_apps_unavailable() {
+        let cases = [
+            (HiddenReason::Hidden, false),
+            (HiddenReason::TryExecMissing("app".to_string()), false),
+            (HiddenReason::NoDisplay, true),
+            (HiddenReason::OnlyShownIn(vec!["KDE".to_string()]), true),
+            (HiddenReason::NotShownIn("GNOME".to_string()), true),
+        ];
+        for (reason, available) in cases {
+            let entry = app(vec![reason.clone()]);
+            assert_eq!(entry.is_available(), available, "{reason:?}");
+            assert!(!entry.is_listed(), "{reason:?}");
+        }
+
+        let entry = app(Vec::new());
+        assert!(entry.is_available());
+        assert!(entry.is_listed());
+    }
+
+    #[test]
+    fn nested_files_get_prefixed_ids() {
+        let dir = test_dir("nested");
+        write_entry(&dir.join("kde4/foo.desktop"), "Foo");
+        write_entry(&dir.join("bar.desktop"), "Bar");
+
+        let mut manager = DesktopEntryManager::new();
+        manager.load_entries_from(std::slice::from_ref(&dir));
+
+        assert_eq!(
+            manager.get_any_entry("kde4-foo.desktop").unwrap().name,
+            "Foo"
+        );
+        assert_eq!(manager.get_any_entry("bar.desktop").unwrap().name, "Bar");
+        assert!(manager.get_any_entry("foo.desktop").is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn user_files_shadow_system_files() {
+        let dir = test_dir("shadowed");
+        let user = dir.join("user");
+        let system = dir.join("system");
+        write_entry(&user.join("editor.desktop"), "User Editor");
+        write_entry(&system.join("editor.desktop"), "System Editor");
+        write_entry(&system.join("viewer.desktop"), "Viewer");
+
+        let mut manager = DesktopEntryManager::new();
+        manager.load_entries_from(&[user.clone(), system.clone()]);
+
+        let editor = manager.get_any_entry("editor.desktop").unwrap();
+        assert_eq!(editor.name, "User Editor");
+        assert_eq!(editor.path, user.join("editor.desktop"));
+        assert!(manager.has_desktop_file("viewer.desktop"));
+
+        let shadowed = manager.get_shadowed_entries();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].id, "editor.desktop");
+        assert_eq!(shadowed[0].path, system.join("editor.desktop"));
+        assert_eq!(shadowed[0].shadowed_by, user.join("editor.desktop"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,314 @@
+use std::fmt;
+
+/// A single line of a keyfile, kept so the file can be written back untouched.
+#[derive(Debug, Clone)]
+enum Line {
+    // Blank lines, comments and anything we don't understand, kept verbatim
+    Other(String),
+    Entry {
+        key: String,
+        value: String,
+        // Original text of the line, dropped once the value is modified
+        raw: Option<String>,
+    },
+}
+
+impl Line {
+    fn is_blank(&self) -> bool {
+        matches!(self, Line::Other(text) if text.trim().is_empty())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Group {
+    name: String,
+    header: String,
+    lines: Vec<Line>,
+}
+
+/// Lossless model of the keyfile format used by `mimeapps.list` and our
+/// settings.
+///
+/// Every group, comment and blank line of the parsed text is preserved, and
+/// edits only touch the lines of the keys that actually changed.
+#[derive(Debug, Clone)]
+pub struct KeyFile {
+    // Lines before the first group header
+    preamble: Vec<Line>,
+    groups: Vec<Group>,
+    trailing_newline: bool,
+}
+
+impl KeyFile {
+    pub fn new() -> Self {
+        Self {
+            preamble: Vec::new(),
+            groups: Vec::new(),
+            trailing_newline: true,
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut keyfile = Self::new();
+        if content.is_empty() {
+            return keyfile;
+        }
+
+        let body = match content.strip_suffix('\n') {
+            Some(body) => body,
+            None => {
+                keyfile.trailing_newline = false;
+                content
+            }
+        };
+
+        for raw in body.split('\n') {
+            let trimmed = raw.trim();
+
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                keyfile.groups.push(Group {
+                    name: trimmed[1..trimmed.len() - 1].to_string(),
+                    header: raw.to_string(),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            let line = match trimmed.split_once('=') {
+                Some((key, value)) if !trimmed.starts_with('#') && !key.trim().is_empty() => {
+                    Line::Entry {
+                        key: key.trim().to_string(),
+                        value: value.trim().to_string(),
+                        raw: Some(raw.to_string()),
+                    }
+                }
+                _ => Line::Other(raw.to_string()),
+            };
+
+            match keyfile.groups.last_mut() {
+                Some(group) => group.lines.push(line),
+                None => keyfile.preamble.push(line),
+            }
+        }
+
+        keyfile
+    }
+
+    /// Returns the value of `key`, the last occurrence winning as in GLib.
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.groups
+            .iter()
+            .filter(|g| g.name == group)
+            .flat_map(|g| g.lines.iter())
+            .filter_map(|line| match line {
+                Line::Entry { key: k, value, .. } if k == key => Some(value.as_str()),
+                _ => None,
+            })
+            .next_back()
+    }
+
+    /// Returns the names of all groups in file order, without duplicates.
+    pub fn group_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for group in &self.groups {
+            if !names.contains(&group.name.as_str()) {
+                names.push(&group.name);
+            }
+        }
+        names
+    }
+
+    /// Returns all entries of `group` in file order, without duplicates.
+    pub fn entries(&self, group: &str) -> Vec<(&str, &str)> {
+        let mut entries: Vec<(&str, &str)> = Vec::new();
+        for line in self
+            .groups
+            .iter()
+            .filter(|g| g.name == group)
+            .flat_map(|g| g.lines.iter())
+        {
+            if let Line::Entry { key, value, .. } = line {
+                match entries.iter_mut().find(|(k, _)| k == key) {
+                    Some(entry) => entry.1 = value,
+                    None => entries.push((key, value)),
+                }
+            }
+        }
+        entries
+    }
+
+    /// Sets `key` in `group`, rewriting its existing line in place or
+    /// appending it after the last entry of the group.
+    pub fn set(&mut self, group: &str, key: &str, new_value: &str) {
+        // Update the last occurrence in place, that's the one readers use
+        let existing = self
+            .groups
+            .iter_mut()
+            .filter(|g| g.name == group)
+            .flat_map(|g| g.lines.iter_mut())
+            .filter(|line| matches!(line, Line::Entry { key: k, .. } if k == key))
+            .last();
+
+        if let Some(Line::Entry { value, raw, .. }) = existing {
+            if value != new_value {
+                *value = new_value.to_string();
+                *raw = None;
+            }
+            return;
+        }
+
+        let entry = Line::Entry {
+            key: key.to_string(),
+            value: new_value.to_string(),
+            raw: None,
+        };
+
+        let group = match self.groups.iter().rposition(|g| g.name == group) {
+            Some(index) => &mut self.groups[index],
+            None => self.push_group(group),
+        };
+
+        // Keep trailing blank lines and comments (usually describing the next
+        // group) after the new entry
+        let insert_at = group
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, Line::Entry { .. }))
+            .or_else(|| group.lines.iter().rposition(|line| !line.is_blank()))
+            .map_or(0, |index| index + 1);
+        group.lines.insert(insert_at, entry);
+    }
+
+    /// Removes every occurrence of `key` from `group`, returning the value
+    /// that was in effect.
+    pub fn remove(&mut self, group: &str, key: &str) -> Option<String> {
+        let previous = self.get(group, key).map(str::to_string);
+
+        for g in self.groups.iter_mut().filter(|g| g.name == group) {
+            g.lines
+                .retain(|line| !matches!(line, Line::Entry { key: k, .. } if k == key));
+        }
+
+        previous
+    }
+
+    fn push_group(&mut self, name: &str) -> &mut Group {
+        // Separate the new group from the previous content with a blank line
+        let has_groups = !self.groups.is_empty();
+        let previous_lines = match self.groups.last_mut() {
+            Some(group) => &mut group.lines,
+            None => &mut self.preamble,
+        };
+        let needs_separator = match previous_lines.last() {
+            Some(line) => !line.is_blank(),
+            None => has_groups,
+        };
+        if needs_separator {
+            previous_lines.push(Line::Other(String::new()));
+        }
+
+        self.groups.push(Group {
+            name: name.to_string(),
+            header: format!("[{}]", name),
+            lines: Vec::new(),
+        });
+        self.groups.last_mut().unwrap()
+    }
+}
+
+impl Default for KeyFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for KeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines: Vec<String> = Vec::new();
+
+        let groups = std::iter::once((None, &self.preamble))
+            .chain(self.groups.iter().map(|g| (Some(&g.header), &g.lines)));
+        for (header, group_lines) in groups {
+            lines.extend(header.cloned());
+            for line in group_lines {
+                lines.push(match line {
+                    Line::Other(text) => text.clone(),
+                    Line::Entry { raw: Some(raw), .. } => raw.clone(),
+                    Line::Entry { key, value, .. } => format!("{}={}", key, value),
+                });
+            }
+        }
+
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, "{}", lines.join("\n"))?;
+        if self.trailing_newline {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "# Written by hand\n\n[Default Applications]\n# Editors\ntext/plain = gedit.desktop;\n\n[X-Custom Group]\nSome Key=some value\n  indented line without a key\n\n[Added Associations]\nimage/png=eog.desktop;\n";
+
+    #[test]
+    fn round_trip_is_lossless() {
+        assert_eq!(KeyFile::parse(CONTENT).to_string(), CONTENT);
+        assert_eq!(KeyFile::parse("a=b").to_string(), "a=b");
+        assert_eq!(KeyFile::parse("").to_string(), "");
+    }
+
+    #[test]
+    fn unknown_groups_are_readable() {
+        let keyfile = KeyFile::parse(CONTENT);
+        assert_eq!(
+            keyfile.group_names(),
+            [
+                "Default Applications",
+                "X-Custom Group",
+                "Added Associations"
+            ]
+        );
+        assert_eq!(
+            keyfile.get("X-Custom Group", "Some Key"),
+            Some("some value")
+        );
+        assert_eq!(
+            keyfile.get("Default Applications", "text/plain"),
+            Some("gedit.desktop;")
+        );
+    }
+
+    #[test]
+    fn edits_only_touch_their_lines() {
+        let mut keyfile = KeyFile::parse(CONTENT);
+        keyfile.set("Added Associations", "image/png", "loupe.desktop;");
+        keyfile.set("Default Applications", "text/markdown", "gedit.desktop;");
+        keyfile.remove("X-Custom Group", "Some Key");
+
+        assert_eq!(
+            keyfile.to_string(),
+            "# Written by hand\n\n[Default Applications]\n# Editors\ntext/plain = gedit.desktop;\ntext/markdown=gedit.desktop;\n\n[X-Custom Group]\n  indented line without a key\n\n[Added Associations]\nimage/png=loupe.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn setting_the_same_value_keeps_the_original_line() {
+        let mut keyfile = KeyFile::parse(CONTENT);
+        keyfile.set("Default Applications", "text/plain", "gedit.desktop;");
+        assert_eq!(keyfile.to_string(), CONTENT);
+    }
+
+    #[test]
+    fn last_duplicate_key_wins() {
+        let keyfile = KeyFile::parse("[G]\nk=1\nk=2\n");
+        assert_eq!(keyfile.get("G", "k"), Some("2"));
+        assert_eq!(keyfile.entries("G"), [("k", "2")]);
+    }
+}
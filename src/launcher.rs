@@ -0,0 +1,347 @@
+// Custom launchers, written as desktop files in $XDG_DATA_HOME/applications
+use crate::keyfile::KeyFile;
+use crate::mime_database::is_valid_mimetype;
+use crate::mimetype_manager::write_atomically;
+use crate::xdg_dirs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DESKTOP_ENTRY: &str = "Desktop Entry";
+
+// Field codes a command may contain, and those the spec deprecated
+const FIELD_CODES: &[char] = &['f', 'F', 'u', 'U', 'i', 'c', 'k', '%'];
+const DEPRECATED_FIELD_CODES: &[char] = &['d', 'D', 'n', 'N', 'v', 'm'];
+
+pub struct Launcher {
+    // The file being edited, `None` until a new launcher is saved
+    pub path: Option<PathBuf>,
+    pub name: String,
+    pub exec: String,
+    pub terminal: bool,
+    pub icon: String,
+    pub mimetypes: Vec<String>,
+    // Keeps the keys we don't edit when the file is written back
+    document: KeyFile,
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            name: String::new(),
+            exec: String::new(),
+            terminal: false,
+            icon: String::new(),
+            mimetypes: Vec::new(),
+            document: KeyFile::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let document = KeyFile::parse(&fs::read_to_string(path)?);
+        let get = |key: &str| unescape_value(document.get(DESKTOP_ENTRY, key).unwrap_or_default());
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            name: get("Name"),
+            exec: get("Exec"),
+            terminal: get("Terminal") == "true",
+            icon: get("Icon"),
+            mimetypes: split_list(document.get(DESKTOP_ENTRY, "MimeType").unwrap_or_default()),
+            document,
+        })
+    }
+
+    /// Checks the launcher against the desktop entry spec, explaining the
+    /// first problem found.
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.name.trim().is_empty() {
+            return Err("The name can't be empty".into());
+        }
+        if self.exec.trim().is_empty() {
+            return Err("The command can't be empty".into());
+        }
+        validate_exec(&self.exec)?;
+
+        if !self.mimetypes.is_empty()
+            && !["%f", "%F", "%u", "%U"]
+                .iter()
+                .any(|code| self.exec.contains(code))
+        {
+            return Err("The command needs %f, %F, %u or %U to receive the files it opens".into());
+        }
+
+        if let Some(mimetype) = self
+            .mimetypes
+            .iter()
+            .find(|mimetype| !is_valid_mimetype(mimetype))
+        {
+            return Err(format!("“{}” is not a valid MIME type", mimetype).into());
+        }
+
+        if self.icon.contains('/') && !Path::new(&self.icon).is_absolute() {
+            return Err("The icon must be an icon name or an absolute path".into());
+        }
+
+        Ok(())
+    }
+
+    /// Validates and writes the launcher, returning its desktop file ID.
+    ///
+    /// New launchers get an ID derived from their name, skipping the ones
+    /// `is_taken` reports so no installed application gets shadowed.
+    pub fn save(
+        &mut self,
+        is_taken: impl Fn(&str) -> bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.validate()?;
+
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => {
+                let dir = xdg_dirs::data_home()
+                    .ok_or("Could not determine the user data directory")?
+                    .join("applications");
+                new_launcher_path(&dir, &self.name, is_taken)
+            }
+        };
+
+        let document = &mut self.document;
+        document.set(DESKTOP_ENTRY, "Type", "Application");
+        document.set(DESKTOP_ENTRY, "Name", &escape_value(self.name.trim()));
+        document.set(DESKTOP_ENTRY, "Exec", &escape_value(self.exec.trim()));
+        document.set(
+            DESKTOP_ENTRY,
+            "Terminal",
+            if self.terminal { "true" } else { "false" },
+        );
+
+        let icon = self.icon.trim();
+        if icon.is_empty() {
+            document.remove(DESKTOP_ENTRY, "Icon");
+        } else {
+            document.set(DESKTOP_ENTRY, "Icon", &escape_value(icon));
+        }
+
+        if self.mimetypes.is_empty() {
+            document.remove(DESKTOP_ENTRY, "MimeType");
+        } else {
+            let mimetypes: String = self
+                .mimetypes
+                .iter()
+                .map(|mimetype| format!("{};", mimetype))
+                .collect();
+            document.set(DESKTOP_ENTRY, "MimeType", &mimetypes);
+        }
+
+        write_atomically(&path, &document.to_string())?;
+
+        let id = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.path = Some(path);
+        Ok(id)
+    }
+}
+
+impl Default for Launcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `path` is a launcher the user can edit, i.e. a desktop file of
+/// their own applications directory.
+pub fn is_user_launcher(path: &Path) -> bool {
+    xdg_dirs::data_home()
+        .is_some_and(|dir| path.parent() == Some(dir.join("applications").as_path()))
+}
+
+// `My Editor` gives `my-editor.desktop`, then `my-editor-2.desktop`...
+fn new_launcher_path(dir: &Path, name: &str, is_taken: impl Fn(&str) -> bool) -> PathBuf {
+    let slug: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug = if slug.is_empty() {
+        "launcher".to_string()
+    } else {
+        slug
+    };
+
+    let mut id = format!("{}.desktop", slug);
+    let mut counter = 2;
+    while is_taken(&id) || dir.join(&id).exists() {
+        id = format!("{}-{}.desktop", slug, counter);
+        counter += 1;
+    }
+    dir.join(id)
+}
+
+// Quoting and field codes, following the Exec key rules of the spec
+fn validate_exec(exec: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // Arguments as written, quotes and escapes included
+    let mut arguments: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() && !in_quotes {
+            if !current.is_empty() {
+                arguments.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        current.push(c);
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if in_quotes => current.extend(chars.next()),
+            '%' if in_quotes => match chars.next() {
+                Some('%') => current.push('%'),
+                _ => return Err("Field codes can't be used inside quotes".into()),
+            },
+            _ => {}
+        }
+    }
+    if in_quotes {
+        return Err("The command has an unterminated quote".into());
+    }
+    arguments.push(current);
+
+    let mut file_codes = 0;
+    for argument in &arguments {
+        let mut chars = argument.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some(code) if DEPRECATED_FIELD_CODES.contains(&code) => {
+                    return Err(format!("The field code %{} is deprecated", code).into());
+                }
+                Some(code) if FIELD_CODES.contains(&code) => {
+                    if matches!(code, 'f' | 'F' | 'u' | 'U') {
+                        file_codes += 1;
+                    }
+                    if matches!(code, 'F' | 'U') && argument.len() != 2 {
+                        return Err(format!("%{} must be an argument of its own", code).into());
+                    }
+                }
+                Some(code) => return Err(format!("Unknown field code %{}", code).into()),
+                None => return Err("The command ends with a lone %".into()),
+            }
+        }
+    }
+
+    if file_codes > 1 {
+        return Err("Only one of %f, %F, %u and %U may be used".into());
+    }
+
+    Ok(())
+}
+
+// Values of the desktop entry format escape these characters
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    if escaped.starts_with(' ') {
+        escaped.replace_range(0..1, "\\s");
+    }
+    escaped
+}
+
+fn unescape_value(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_field_codes_follow_the_spec() {
+        let cases = [
+            ("app %f", true),
+            ("app --open %U", true),
+            ("app \"100%%\" %u", true),
+            ("app %% %i %c %k", true),
+            ("app \"%f\"", false),
+            ("app \"%%\"%f", true),
+            ("app --files=%F", false),
+            ("app \"\"%F", false),
+            ("app %f %u", false),
+            ("app %F %F", false),
+            ("app %d", false),
+            ("app %m %f", false),
+            ("app %x", false),
+            ("app \"file %f", false),
+            ("app \"unterminated", false),
+            ("app %", false),
+            ("app \"%\"", false),
+        ];
+        for (exec, valid) in cases {
+            assert_eq!(validate_exec(exec).is_ok(), valid, "{exec}");
+        }
+    }
+
+    #[test]
+    fn launchers_opening_files_need_a_file_code() {
+        let mut launcher = Launcher::new();
+        launcher.name = "App".to_string();
+        launcher.exec = "app".to_string();
+        assert!(launcher.validate().is_ok());
+
+        launcher.mimetypes = vec!["text/plain".to_string()];
+        assert!(launcher.validate().is_err());
+
+        launcher.exec = "app %F".to_string();
+        assert!(launcher.validate().is_ok());
+
+        launcher.exec = "app \"%F\"".to_string();
+        assert!(launcher.validate().is_err());
+
+        launcher.mimetypes = vec!["not a type".to_string()];
+        assert!(launcher.validate().is_err());
+    }
+}
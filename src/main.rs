@@ -1,21 +1,56 @@
+mod app_object;
+mod cli;
 mod desktop_entries;
+mod keyfile;
+mod launcher;
+mod mime_database;
 mod mimetype_manager;
+mod roles;
+mod search;
+mod settings;
 mod window;
+mod xdg_dirs;
 
 use adw::Application;
-use gtk::glib;
 use gtk::prelude::*;
+use gtk::{gio, glib};
 use window::Window;
 
 const APP_ID: &str = "com.github.arkye03.app_defaulter";
 
 fn main() -> glib::ExitCode {
+    // Subcommands run headless, without initializing GTK
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_command_line(&args) {
+        return glib::ExitCode::from(cli::run(&args));
+    }
+
     // Create a new application
-    let app = Application::builder().application_id(APP_ID).build();
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(gio::ApplicationFlags::HANDLES_OPEN)
+        .build();
+
+    app.set_accels_for_action("win.save-changes", &["<Control>s"]);
+    app.set_accels_for_action("win.undo", &["<Control>z"]);
+    app.set_accels_for_action("win.redo", &["<Control><Shift>z"]);
 
     // Connect to "activate" signal of `app`
     app.connect_activate(build_ui);
 
+    // Files passed on the command line show what opens them
+    app.connect_open(|app, files, _| {
+        let window = app
+            .active_window()
+            .and_downcast::<Window>()
+            .unwrap_or_else(|| Window::new(app));
+        window.present();
+
+        for path in files.iter().filter_map(|file| file.path()) {
+            window.show_file_handler(&path);
+        }
+    });
+
     // Run the application
     app.run()
 }
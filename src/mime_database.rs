@@ -0,0 +1,850 @@
+// Read-only view of the shared MIME-info database (usually /usr/share/mime)
+use crate::xdg_dirs;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Files are never read further than this to sniff them
+const MAX_SNIFF_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+struct Glob {
+    weight: u32,
+    pattern: String,
+    mimetype: String,
+    case_sensitive: bool,
+}
+
+// One line of a `magic` section: `[indent]>offset=value[&mask][~word-size][+range]`
+#[derive(Debug, Clone)]
+struct MagicRule {
+    indent: usize,
+    offset: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    range: usize,
+}
+
+impl MagicRule {
+    fn matches(&self, data: &[u8]) -> bool {
+        (self.offset..self.offset + self.range).any(|start| {
+            let Some(window) = data.get(start..start + self.value.len()) else {
+                return false;
+            };
+            match &self.mask {
+                Some(mask) => window
+                    .iter()
+                    .zip(&self.value)
+                    .zip(mask)
+                    .all(|((byte, value), mask)| byte & mask == value & mask),
+                None => window == self.value.as_slice(),
+            }
+        })
+    }
+
+    // How far into a file this rule looks
+    fn extent(&self) -> usize {
+        self.offset + self.range + self.value.len()
+    }
+}
+
+// A `[priority:mimetype]` section of a `magic` file
+#[derive(Debug, Clone)]
+struct MagicEntry {
+    priority: u32,
+    mimetype: String,
+    rules: Vec<MagicRule>,
+}
+
+impl MagicEntry {
+    fn matches(&self, data: &[u8]) -> bool {
+        let mut i = 0;
+        while i < self.rules.len() {
+            let (matched, next) = self.match_subtree(i, data);
+            if matched {
+                return true;
+            }
+            i = next;
+        }
+        false
+    }
+
+    // A rule matches if it does and, when it has nested rules, one of them
+    // does too. Returns the index of the rule following the subtree.
+    fn match_subtree(&self, i: usize, data: &[u8]) -> (bool, usize) {
+        let indent = self.rules[i].indent;
+        let mut next = i + 1;
+        let mut has_children = false;
+        let mut child_matched = false;
+
+        while next < self.rules.len() && self.rules[next].indent > indent {
+            let (matched, after) = self.match_subtree(next, data);
+            has_children = true;
+            child_matched |= matched;
+            next = after;
+        }
+
+        let matched = self.rules[i].matches(data) && (!has_children || child_matched);
+        (matched, next)
+    }
+}
+
+pub struct MimeDatabase {
+    // `mime` directories, the most important first
+    dirs: Vec<PathBuf>,
+    // Languages to look descriptions up in, in order of preference
+    locales: Vec<String>,
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, Vec<String>>,
+    icons: HashMap<String, String>,
+    generic_icons: HashMap<String, String>,
+    globs: Vec<Glob>,
+    // Sorted by priority, the highest first
+    magic: Vec<MagicEntry>,
+    mimetypes: BTreeSet<String>,
+    // Descriptions are read from one XML file per type, only when needed
+    descriptions: RefCell<HashMap<String, Option<String>>>,
+}
+
+impl MimeDatabase {
+    pub fn new() -> Self {
+        Self {
+            dirs: Vec::new(),
+            locales: Vec::new(),
+            aliases: HashMap::new(),
+            parents: HashMap::new(),
+            icons: HashMap::new(),
+            generic_icons: HashMap::new(),
+            globs: Vec::new(),
+            magic: Vec::new(),
+            mimetypes: BTreeSet::new(),
+            descriptions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Loads the database from `$XDG_DATA_HOME/mime` and every
+    /// `$XDG_DATA_DIRS/mime`.
+    pub fn load() -> Self {
+        let mut database = Self::new();
+
+        database.dirs = xdg_dirs::data_home()
+            .into_iter()
+            .chain(xdg_dirs::data_dirs())
+            .map(|dir| dir.join("mime"))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        database.locales = locale_variants(&freedesktop_desktop_entry::get_languages_from_env());
+
+        // Go from the least important directory up, so the important ones win
+        for dir in database.dirs.clone().iter().rev() {
+            let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap_or_default();
+
+            for (alias, canonical) in pairs(&read("aliases"), ' ') {
+                database
+                    .aliases
+                    .insert(alias.to_string(), canonical.to_string());
+            }
+
+            for (child, parent) in pairs(&read("subclasses"), ' ') {
+                let parents = database.parents.entry(child.to_string()).or_default();
+                if !parents.iter().any(|p| p == parent) {
+                    parents.push(parent.to_string());
+                }
+            }
+
+            for (mimetype, icon) in pairs(&read("icons"), ':') {
+                database
+                    .icons
+                    .insert(mimetype.to_string(), icon.to_string());
+            }
+
+            for (mimetype, icon) in pairs(&read("generic-icons"), ':') {
+                database
+                    .generic_icons
+                    .insert(mimetype.to_string(), icon.to_string());
+            }
+
+            database.read_globs(&read("globs2"));
+            database.read_magic(&fs::read(dir.join("magic")).unwrap_or_default());
+
+            for mimetype in read("types").lines().map(str::trim) {
+                if !mimetype.is_empty() {
+                    database.mimetypes.insert(mimetype.to_string());
+                }
+            }
+        }
+
+        database
+            .magic
+            .sort_by_key(|entry| std::cmp::Reverse(entry.priority));
+        database
+    }
+
+    // Lines look like `weight:mimetype:glob[:flags]`
+    fn read_globs(&mut self, content: &str) {
+        for line in content.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(4, ':');
+            let (Some(weight), Some(mimetype), Some(pattern)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let flags = fields.next().unwrap_or_default();
+
+            // A more important directory dropping the globs of a type
+            if pattern == "__NOGLOBS__" {
+                self.globs.retain(|glob| glob.mimetype != mimetype);
+                continue;
+            }
+
+            self.globs.push(Glob {
+                weight: weight.parse().unwrap_or(50),
+                pattern: pattern.to_string(),
+                mimetype: mimetype.to_string(),
+                case_sensitive: flags.split(',').any(|flag| flag == "cs"),
+            });
+        }
+    }
+
+    // The binary `magic` file: a `MIME-Magic\0\n` header, then sections
+    // starting with `[priority:mimetype]` followed by one rule per line
+    fn read_magic(&mut self, content: &[u8]) {
+        let Some(mut rest) = content.strip_prefix(b"MIME-Magic\0\n") else {
+            return;
+        };
+
+        let mut entries: Vec<MagicEntry> = Vec::new();
+        while let Some(header) = rest.strip_prefix(b"[") {
+            let Some(end) = header.iter().position(|&b| b == b'\n') else {
+                break;
+            };
+            let line = String::from_utf8_lossy(&header[..end]);
+            rest = &header[end + 1..];
+
+            let Some((priority, mimetype)) = line.trim_end_matches(']').split_once(':') else {
+                break;
+            };
+            let mut entry = MagicEntry {
+                priority: priority.parse().unwrap_or(50),
+                mimetype: mimetype.to_string(),
+                rules: Vec::new(),
+            };
+
+            while !rest.is_empty() && rest[0] != b'[' {
+                match parse_magic_rule(rest) {
+                    Some((rule, after)) => {
+                        entry.rules.extend(rule);
+                        rest = after;
+                    }
+                    // The rest of the file can't be trusted
+                    None => {
+                        rest = &[];
+                        break;
+                    }
+                }
+            }
+            entries.push(entry);
+        }
+
+        // A more important directory replaces the magic of a type entirely
+        for entry in &entries {
+            self.magic.retain(|magic| magic.mimetype != entry.mimetype);
+        }
+        entries.retain(|entry| !entry.rules.is_empty());
+        self.magic.extend(entries);
+    }
+
+    /// Every MIME type known to the database, sorted.
+    pub fn get_all_mimetypes(&self) -> Vec<String> {
+        self.mimetypes.iter().cloned().collect()
+    }
+
+    /// Resolves an alias such as `application/x-pdf` to its canonical type.
+    pub fn canonical<'a>(&'a self, mimetype: &'a str) -> &'a str {
+        self.aliases
+            .get(mimetype)
+            .map(String::as_str)
+            .unwrap_or(mimetype)
+    }
+
+    /// Aliases known for the canonical type `mimetype`.
+    pub fn aliases_of(&self, mimetype: &str) -> Vec<&str> {
+        let mut aliases: Vec<&str> = self
+            .aliases
+            .iter()
+            .filter(|(_, canonical)| canonical.as_str() == mimetype)
+            .map(|(alias, _)| alias.as_str())
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// Direct parents of `mimetype`, including the implicit `text/plain`
+    /// parent of every `text/*` type.
+    pub fn parents(&self, mimetype: &str) -> Vec<String> {
+        let mimetype = self.canonical(mimetype);
+        let mut parents = self.parents.get(mimetype).cloned().unwrap_or_default();
+
+        if mimetype.starts_with("text/")
+            && mimetype != "text/plain"
+            && !parents.iter().any(|p| p == "text/plain")
+        {
+            parents.push("text/plain".to_string());
+        }
+
+        parents
+    }
+
+    /// All types `mimetype` inherits from, the closest first.
+    pub fn ancestors(&self, mimetype: &str) -> Vec<String> {
+        let mimetype = self.canonical(mimetype);
+        let mut ancestors: Vec<String> = Vec::new();
+        let mut queue = vec![mimetype.to_string()];
+
+        while !queue.is_empty() {
+            let mut next = Vec::new();
+            for current in queue {
+                for parent in self.parents(&current) {
+                    let parent = self.canonical(&parent).to_string();
+                    if parent != mimetype && !ancestors.contains(&parent) {
+                        ancestors.push(parent.clone());
+                        next.push(parent);
+                    }
+                }
+            }
+            queue = next;
+        }
+
+        ancestors
+    }
+
+    /// `mimetype`, its canonical type and aliases, then every ancestor with
+    /// its aliases: the order in which to look for a handler.
+    pub fn fallback_chain(&self, mimetype: &str) -> Vec<String> {
+        let mut chain = vec![mimetype.to_string()];
+
+        let related =
+            std::iter::once(self.canonical(mimetype).to_string()).chain(self.ancestors(mimetype));
+        for related in related {
+            let aliases: Vec<String> = self
+                .aliases_of(&related)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            for candidate in std::iter::once(related).chain(aliases) {
+                if !chain.contains(&candidate) {
+                    chain.push(candidate);
+                }
+            }
+        }
+
+        chain
+    }
+
+    pub fn is_subclass_of(&self, mimetype: &str, parent: &str) -> bool {
+        let parent = self.canonical(parent);
+        self.canonical(mimetype) == parent || self.ancestors(mimetype).iter().any(|a| a == parent)
+    }
+
+    /// The human readable description (e.g. "PDF document"), in the user's
+    /// language when a translation is available.
+    pub fn description(&self, mimetype: &str) -> Option<String> {
+        let mimetype = self.canonical(mimetype);
+
+        if let Some(description) = self.descriptions.borrow().get(mimetype) {
+            return description.clone();
+        }
+
+        let description = self.dirs.iter().find_map(|dir| {
+            let content = fs::read_to_string(dir.join(format!("{}.xml", mimetype))).ok()?;
+            localized_comment(&content, &self.locales)
+        });
+
+        self.descriptions
+            .borrow_mut()
+            .insert(mimetype.to_string(), description.clone());
+        description
+    }
+
+    /// A themed icon name for `mimetype`, falling back to the generic icon
+    /// of its media type.
+    pub fn icon_name(&self, mimetype: &str) -> String {
+        let mimetype = self.canonical(mimetype);
+
+        if let Some(icon) = self.icons.get(mimetype) {
+            return icon.clone();
+        }
+        if let Some(icon) = self.generic_icons.get(mimetype) {
+            return icon.clone();
+        }
+
+        let media = mimetype.split('/').next().unwrap_or_default();
+        match media {
+            "text" | "image" | "audio" | "video" | "font" => format!("{}-x-generic", media),
+            _ => "application-x-generic".to_string(),
+        }
+    }
+
+    /// Types matching `file_name` by glob, the most likely first.
+    pub fn mimetypes_for_file_name(&self, file_name: &str) -> Vec<String> {
+        let lowercase_name = file_name.to_lowercase();
+
+        // Case-sensitive globs go first, so `*.C` isn't taken for `*.c`
+        let mut matches: Vec<&Glob> = self
+            .globs
+            .iter()
+            .filter(|glob| glob.case_sensitive && glob_match(&glob.pattern, file_name))
+            .collect();
+        if matches.is_empty() {
+            matches = self
+                .globs
+                .iter()
+                .filter(|glob| {
+                    !glob.case_sensitive
+                        && glob_match(&glob.pattern.to_lowercase(), &lowercase_name)
+                })
+                .collect();
+        }
+
+        // The highest weight wins, then the longest (most specific) pattern
+        let Some(best) = matches
+            .iter()
+            .map(|glob| (glob.weight, glob.pattern.len()))
+            .max()
+        else {
+            return Vec::new();
+        };
+
+        let mut mimetypes: Vec<String> = Vec::new();
+        for glob in matches {
+            if (glob.weight, glob.pattern.len()) == best && !mimetypes.contains(&glob.mimetype) {
+                mimetypes.push(glob.mimetype.clone());
+            }
+        }
+        mimetypes
+    }
+
+    /// The type of the file at `path`, sniffed the way the shared MIME-info
+    /// spec recommends: by name first, using its contents to choose between
+    /// equally likely names or when the name says nothing.
+    pub fn mimetype_for_file(&self, path: &Path) -> String {
+        if path.is_dir() {
+            return "inode/directory".to_string();
+        }
+
+        let by_name = path
+            .file_name()
+            .map(|name| self.mimetypes_for_file_name(&name.to_string_lossy()))
+            .unwrap_or_default();
+        if let [mimetype] = by_name.as_slice() {
+            return mimetype.clone();
+        }
+
+        let data = read_head(path, self.magic_extent());
+        let by_content = self.mimetype_for_data(&data);
+
+        if let Some(sniffed) = &by_content {
+            // Prefer the name when it is a more precise type of the contents
+            if let Some(mimetype) = by_name
+                .iter()
+                .find(|mimetype| self.is_subclass_of(mimetype, sniffed))
+            {
+                return mimetype.clone();
+            }
+        }
+        if let Some(mimetype) = by_name.into_iter().next() {
+            return mimetype;
+        }
+
+        match by_content {
+            Some(mimetype) => mimetype,
+            None if data.is_empty() => "application/x-zerosize".to_string(),
+            None if looks_like_text(&data) => "text/plain".to_string(),
+            None => "application/octet-stream".to_string(),
+        }
+    }
+
+    /// The type whose magic matches `data` with the highest priority.
+    pub fn mimetype_for_data(&self, data: &[u8]) -> Option<String> {
+        self.magic
+            .iter()
+            .find(|entry| entry.matches(data))
+            .map(|entry| entry.mimetype.clone())
+    }
+
+    // How much of a file the magic rules need to see
+    fn magic_extent(&self) -> usize {
+        self.magic
+            .iter()
+            .flat_map(|entry| &entry.rules)
+            .map(MagicRule::extent)
+            .max()
+            .unwrap_or_default()
+            .min(MAX_SNIFF_LEN)
+    }
+}
+
+impl Default for MimeDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Parses one magic rule, returning it (unless its word size makes it
+// unusable) and the input following its line
+fn parse_magic_rule(input: &[u8]) -> Option<(Option<MagicRule>, &[u8])> {
+    let (indent, input) = parse_number(input).unwrap_or((0, input));
+    let input = input.strip_prefix(b">")?;
+    let (offset, input) = parse_number(input)?;
+    let input = input.strip_prefix(b"=")?;
+
+    let (length, input) = input.split_first_chunk::<2>()?;
+    let length = usize::from(u16::from_be_bytes(*length));
+    let (value, mut input) = input.split_at_checked(length)?;
+    let mut value = value.to_vec();
+
+    let mut mask = None;
+    if let Some(rest) = input.strip_prefix(b"&") {
+        let (bytes, rest) = rest.split_at_checked(length)?;
+        mask = Some(bytes.to_vec());
+        input = rest;
+    }
+
+    let mut word_size = 1;
+    if let Some(rest) = input.strip_prefix(b"~") {
+        (word_size, input) = parse_number(rest)?;
+    }
+
+    let mut range = 1;
+    if let Some(rest) = input.strip_prefix(b"+") {
+        (range, input) = parse_number(rest)?;
+    }
+
+    // Skip anything this version of the format doesn't know about
+    let end = input.iter().position(|&b| b == b'\n')?;
+    let input = &input[end + 1..];
+
+    // Words are stored big-endian and compared in host byte order
+    if word_size > 1 && cfg!(target_endian = "little") {
+        if length % word_size != 0 {
+            return Some((None, input));
+        }
+        for bytes in std::iter::once(&mut value).chain(mask.as_mut()) {
+            bytes.chunks_mut(word_size).for_each(|word| word.reverse());
+        }
+    }
+
+    let rule = MagicRule {
+        indent,
+        offset,
+        value,
+        mask,
+        range: range.max(1),
+    };
+    Some((Some(rule), input))
+}
+
+fn parse_number(input: &[u8]) -> Option<(usize, &[u8])> {
+    let digits = input.iter().take_while(|b| b.is_ascii_digit()).count();
+    let number = std::str::from_utf8(&input[..digits]).ok()?.parse().ok()?;
+    Some((number, &input[digits..]))
+}
+
+// Up to `len` bytes from the start of the file
+fn read_head(path: &Path, len: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(len.max(512) as u64).read_to_end(&mut data);
+    }
+    data
+}
+
+// Like shared-mime-info: no control characters besides whitespace
+fn looks_like_text(data: &[u8]) -> bool {
+    data.iter()
+        .all(|&b| b >= 0x20 || matches!(b, b'\n' | b'\r' | b'\t' | 0x0c | 0x1b))
+}
+
+/// Whether `mimetype` has the `media/subtype` form, without characters that
+/// would break `mimeapps.list`.
+pub fn is_valid_mimetype(mimetype: &str) -> bool {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_graphic() && !matches!(c, '/' | ';' | '=' | '[' | ']'))
+    };
+
+    mimetype
+        .split_once('/')
+        .is_some_and(|(media, subtype)| valid_part(media) && valid_part(subtype))
+}
+
+// Non-comment lines split in two at `separator`
+fn pairs(content: &str, separator: char) -> impl Iterator<Item = (&str, &str)> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(move |line| line.trim().split_once(separator))
+}
+
+// `de_DE.UTF-8@euro` gives `de_DE@euro`, `de_DE`, `de@euro` and `de`, like
+// the desktop entry spec matches locales
+fn locale_variants(locales: &[String]) -> Vec<String> {
+    let mut variants = Vec::new();
+
+    for locale in locales.iter().flat_map(|l| l.split(':')) {
+        let (locale, modifier) = match locale.split_once('@') {
+            Some((locale, modifier)) => (locale, Some(modifier)),
+            None => (locale, None),
+        };
+        let locale = locale.split('.').next().unwrap_or_default();
+        let language = locale.split('_').next().unwrap_or_default();
+        if language.is_empty() || language == "C" || language == "POSIX" {
+            continue;
+        }
+
+        let mut candidates = Vec::new();
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{}@{}", locale, modifier));
+        }
+        candidates.push(locale.to_string());
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{}@{}", language, modifier));
+        }
+        candidates.push(language.to_string());
+
+        for candidate in candidates {
+            if !variants.contains(&candidate) {
+                variants.push(candidate);
+            }
+        }
+    }
+
+    variants
+}
+
+// Picks the `<comment>` of a MIME type XML file best matching `locales`
+fn localized_comment(xml: &str, locales: &[String]) -> Option<String> {
+    let mut untranslated = None;
+    let mut translations: HashMap<String, String> = HashMap::new();
+
+    let mut rest = xml;
+    while let Some(start) = rest.find("<comment") {
+        rest = &rest[start + "<comment".len()..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let attributes = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+
+        let Some(text_end) = rest.find("</comment>") else {
+            break;
+        };
+        let text = unescape_xml(rest[..text_end].trim());
+        rest = &rest[text_end..];
+
+        match xml_attribute(attributes, "xml:lang") {
+            Some(lang) => {
+                translations.insert(lang.to_string(), text);
+            }
+            None => untranslated = untranslated.or(Some(text)),
+        }
+    }
+
+    locales
+        .iter()
+        .find_map(|locale| translations.remove(locale))
+        .or(untranslated)
+}
+
+fn xml_attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let start = attributes.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = attributes[start..].chars().next()?;
+    let value = &attributes[start + 1..];
+    Some(&value[..value.find(quote)?])
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Shell-style matching of `*`, `?` and `[...]`, as used by globs2
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position to go back to after the last `*`, for backtracking
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                '*' => {
+                    star = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                '?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                '[' => match match_class(&pattern, p, text[t]) {
+                    Some((true, next)) => {
+                        p = next;
+                        t += 1;
+                        continue;
+                    }
+                    Some((false, _)) => {}
+                    // Unterminated, so just a `[`
+                    None if text[t] == '[' => {
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                    None => {}
+                },
+                c if c == text[t] => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        match star {
+            Some((star_p, star_t)) => {
+                p = star_p + 1;
+                t = star_t + 1;
+                star = Some((star_p, star_t + 1));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Matches `c` against the `[...]` class starting at `start`, returning
+// whether it matched and the position after the class
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() && (first || pattern[i] != ']') {
+        first = false;
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            matched |= pattern[i] <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+
+    // An unterminated class is left to the caller to match as a plain `[`
+    if i >= pattern.len() {
+        return None;
+    }
+    Some((matched != negated, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(globs2: &str) -> MimeDatabase {
+        let mut database = MimeDatabase::new();
+        database.read_globs(globs2);
+        database
+    }
+
+    #[test]
+    fn heavier_globs_win() {
+        let database = database(
+            "# comment\n\
+             10:application/x-trash:*~\n\
+             50:text/x-backup:*.txt~\n\
+             50:text/plain:*.txt\n\
+             20:application/x-generic:*.txt\n",
+        );
+        assert_eq!(
+            database.mimetypes_for_file_name("notes.txt"),
+            ["text/plain"]
+        );
+        assert_eq!(
+            database.mimetypes_for_file_name("notes.txt~"),
+            ["text/x-backup"]
+        );
+    }
+
+    #[test]
+    fn longer_patterns_break_weight_ties() {
+        let database = database(
+            "50:application/gzip:*.gz\n\
+             50:application/x-compressed-tar:*.tar.gz\n",
+        );
+        assert_eq!(
+            database.mimetypes_for_file_name("src.tar.gz"),
+            ["application/x-compressed-tar"]
+        );
+        assert_eq!(
+            database.mimetypes_for_file_name("log.gz"),
+            ["application/gzip"]
+        );
+    }
+
+    #[test]
+    fn case_sensitive_globs_go_first() {
+        let database = database(
+            "50:text/x-csrc:*.c\n\
+             50:text/x-c++src:*.C:cs\n",
+        );
+        assert_eq!(
+            database.mimetypes_for_file_name("main.C"),
+            ["text/x-c++src"]
+        );
+        assert_eq!(database.mimetypes_for_file_name("main.c"), ["text/x-csrc"]);
+    }
+
+    #[test]
+    fn other_globs_ignore_case() {
+        let database = database("50:image/jpeg:*.jpg\n");
+        assert_eq!(database.mimetypes_for_file_name("IMG.JPG"), ["image/jpeg"]);
+    }
+
+    #[test]
+    fn unterminated_classes_match_literally() {
+        assert!(glob_match("*.[ch]", "main.h"));
+        assert!(!glob_match("*.[ch]", "main.o"));
+        assert!(glob_match("*[abc", "x[abc"));
+        assert!(!glob_match("*[abc", "xa"));
+    }
+
+    #[test]
+    fn noglobs_drops_earlier_globs() {
+        let database = database(
+            "50:text/x-foo:*.foo\n\
+             50:text/x-foo:__NOGLOBS__\n",
+        );
+        assert!(database.mimetypes_for_file_name("a.foo").is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+/// An edit of the user's `mimeapps.list`, staged until it's saved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    SetDefault {
+        mimetype: String,
+        desktop_file: String,
+    },
+    AddFallback {
+        mimetype: String,
+        desktop_file: String,
+    },
+    MoveDefault {
+        mimetype: String,
+        desktop_file: String,
+        position: usize,
+    },
+    UnsetDefaults {
+        mimetype: String,
+    },
+    RemoveDefault {
+        mimetype: String,
+        desktop_file: String,
+    },
+    AddAssociation {
+        mimetype: String,
+        desktop_file: String,
+    },
+    RemoveAssociation {
+        mimetype: String,
+        desktop_file: String,
+    },
+    ResetAssociation {
+        mimetype: String,
+        desktop_file: String,
+    },
+    // Puts back a whole list, for undoing and redoing or to keep ours over
+    // another program's version
+    Replace {
+        mimetype: String,
+        kind: ChangeKind,
+        desktop_files: Vec<String>,
+    },
+}
+
+impl Operation {
+    /// Whether applying the operation can change the list of `kind` for
+    /// `mimetype`.
+    pub fn touches(&self, mimetype: &str, kind: ChangeKind) -> bool {
+        let (target, kinds): (&str, &[ChangeKind]) = match self {
+            Operation::SetDefault { mimetype, .. }
+            | Operation::AddFallback { mimetype, .. }
+            | Operation::MoveDefault { mimetype, .. }
+            | Operation::UnsetDefaults { mimetype }
+            | Operation::RemoveDefault { mimetype, .. } => (mimetype, &[ChangeKind::Defaults]),
+            Operation::AddAssociation { mimetype, .. }
+            | Operation::RemoveAssociation { mimetype, .. }
+            | Operation::ResetAssociation { mimetype, .. } => (
+                mimetype,
+                &[
+                    ChangeKind::AddedAssociations,
+                    ChangeKind::RemovedAssociations,
+                ],
+            ),
+            Operation::Replace { mimetype, kind, .. } => (mimetype, std::slice::from_ref(kind)),
+        };
+        target == mimetype && kinds.contains(&kind)
+    }
+}
+
+/// The group of `mimeapps.list` a change is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Defaults,
+    AddedAssociations,
+    RemovedAssociations,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Defaults => write!(f, "Default applications"),
+            ChangeKind::AddedAssociations => write!(f, "Added associations"),
+            ChangeKind::RemovedAssociations => write!(f, "Removed associations"),
+        }
+    }
+}
+
+/// How saving would change the applications listed for a type.
+#[derive(Debug, Clone)]
+pub struct PendingChange {
+    pub mimetype: String,
+    pub kind: ChangeKind,
+    // Desktop file IDs in the saved file, then in the staged version
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
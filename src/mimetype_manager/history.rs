@@ -0,0 +1,166 @@
+use super::changes::{ChangeKind, PendingChange};
+use crate::keyfile::KeyFile;
+use crate::xdg_dirs;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Older entries are dropped past this
+const MAX_ENTRIES: usize = 200;
+
+/// A change made from the window or the command line, with what it did to
+/// each type.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    // Identifies the entry while the program runs, whatever is added or
+    // dropped around it. Not saved.
+    pub id: u64,
+    // Seconds since the Unix epoch
+    pub time: u64,
+    pub description: String,
+    pub changes: Vec<PendingChange>,
+    // Undone entries can be redone, until something else is changed
+    pub undone: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(description: &str, changes: Vec<PendingChange>) -> Self {
+        Self {
+            id: next_id(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            description: description.to_string(),
+            changes,
+            undone: false,
+        }
+    }
+}
+
+fn next_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    Some(xdg_dirs::state_home()?.join("pick_def_app").join("history"))
+}
+
+// Each entry is a group, its changes keys like `Old:Default:text/plain`
+fn kind_key(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::Defaults => "Default",
+        ChangeKind::AddedAssociations => "Added",
+        ChangeKind::RemovedAssociations => "Removed",
+    }
+}
+
+fn parse_kind(key: &str) -> Option<ChangeKind> {
+    match key {
+        "Default" => Some(ChangeKind::Defaults),
+        "Added" => Some(ChangeKind::AddedAssociations),
+        "Removed" => Some(ChangeKind::RemovedAssociations),
+        _ => None,
+    }
+}
+
+/// Reads the journal, oldest entry first. A missing or unreadable file is
+/// an empty history.
+pub fn load(path: &Path) -> Vec<HistoryEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let document = KeyFile::parse(&content);
+
+    let mut entries = Vec::new();
+    for group in document.group_names() {
+        let mut entry = HistoryEntry {
+            id: next_id(),
+            time: document
+                .get(group, "Time")
+                .and_then(|time| time.parse().ok())
+                .unwrap_or_default(),
+            description: document
+                .get(group, "Description")
+                .unwrap_or_default()
+                .to_string(),
+            changes: Vec::new(),
+            undone: document.get(group, "Undone") == Some("true"),
+        };
+
+        for (key, value) in document.entries(group) {
+            let mut parts = key.splitn(3, ':');
+            let (Some(side), Some(kind), Some(mimetype)) = (
+                parts.next(),
+                parts.next().and_then(parse_kind),
+                parts.next(),
+            ) else {
+                continue;
+            };
+            let apps: Vec<String> = value
+                .split(';')
+                .filter(|app| !app.is_empty())
+                .map(str::to_string)
+                .collect();
+
+            let change = match entry
+                .changes
+                .iter_mut()
+                .find(|change| change.kind == kind && change.mimetype == mimetype)
+            {
+                Some(change) => change,
+                None => {
+                    entry.changes.push(PendingChange {
+                        mimetype: mimetype.to_string(),
+                        kind,
+                        old: Vec::new(),
+                        new: Vec::new(),
+                    });
+                    entry.changes.last_mut().expect("A change was just added")
+                }
+            };
+            match side {
+                "Old" => change.old = apps,
+                "New" => change.new = apps,
+                _ => {}
+            }
+        }
+
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Writes the journal, keeping only the newest entries.
+pub fn save(path: &Path, entries: &[HistoryEntry]) -> io::Result<()> {
+    let mut document = KeyFile::new();
+    let skipped = entries.len().saturating_sub(MAX_ENTRIES);
+
+    for (index, entry) in entries.iter().skip(skipped).enumerate() {
+        let group = format!("Entry {}", index + 1);
+        document.set(&group, "Time", &entry.time.to_string());
+        document.set(&group, "Description", &entry.description);
+        document.set(
+            &group,
+            "Undone",
+            if entry.undone { "true" } else { "false" },
+        );
+
+        for change in &entry.changes {
+            let kind = kind_key(change.kind);
+            for (side, apps) in [("Old", &change.old), ("New", &change.new)] {
+                let value: String = apps.iter().map(|app| format!("{};", app)).collect();
+                document.set(
+                    &group,
+                    &format!("{}:{}:{}", side, kind, change.mimetype),
+                    &value,
+                );
+            }
+        }
+    }
+
+    super::storage::write_atomically(path, &document.to_string())
+}
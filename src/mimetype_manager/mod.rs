@@ -0,0 +1,1171 @@
+mod changes;
+mod history;
+mod resolver;
+mod schemes;
+mod storage;
+
+pub use changes::{ChangeKind, Operation, PendingChange};
+pub use history::HistoryEntry;
+pub use resolver::EffectiveDefault;
+pub use schemes::{LINKED_TYPES, SplitHandlers, WEB_BROWSER_TYPES, scheme, scheme_name};
+pub use storage::{Backup, write_atomically};
+
+use crate::keyfile::KeyFile;
+use crate::mime_database::MimeDatabase;
+use crate::xdg_dirs;
+use resolver::MimeappsLayer;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+
+const CHANGE_KINDS: [ChangeKind; 3] = [
+    ChangeKind::Defaults,
+    ChangeKind::AddedAssociations,
+    ChangeKind::RemovedAssociations,
+];
+
+/// Saving was refused because another program changed the user's
+/// `mimeapps.list` since it was loaded, and writing would undo that change.
+#[derive(Debug)]
+pub struct ExternalChangeError {
+    pub path: PathBuf,
+}
+
+impl fmt::Display for ExternalChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} was changed by another program", self.path.display())
+    }
+}
+
+impl std::error::Error for ExternalChangeError {}
+
+/// Reloading was refused because another program changed types the staged
+/// changes are about, and replaying them on top would silently undo that.
+#[derive(Debug)]
+pub struct ConflictError {
+    pub path: PathBuf,
+    // What the other program wrote (`old`) and the staged version (`new`)
+    pub conflicts: Vec<PendingChange>,
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} was changed by another program for {} types with unsaved changes",
+            self.path.display(),
+            self.conflicts.len()
+        )
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+pub struct MimetypeManager {
+    user_config_path: PathBuf,
+    // Full contents of the file, so saving doesn't drop what we don't manage,
+    // with the staged changes applied
+    document: KeyFile,
+    // The file as last read or written
+    saved_document: KeyFile,
+    // Changes since the last save, replayed when the file is read again
+    operations: Vec<Operation>,
+    // Journal of the changes made, oldest first, and as of the last save
+    history: Vec<HistoryEntry>,
+    saved_history: Vec<HistoryEntry>,
+    history_path: Option<PathBuf>,
+    // Where the file is copied to before being overwritten
+    backup_dir: Option<PathBuf>,
+    // What the file held when we last read or wrote it, `None` if missing
+    disk_fingerprint: Option<u64>,
+    // Desktop files per MIME type, the preferred one first
+    current_defaults: HashMap<String, Vec<String>>,
+    added_associations: HashMap<String, Vec<String>>,
+    removed_associations: HashMap<String, Vec<String>>,
+    // Every mimeapps.list in precedence order, the user's one included
+    layers: Vec<MimeappsLayer>,
+}
+
+impl MimetypeManager {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let user_config_path = xdg_dirs::config_home()
+            .ok_or("Could not determine the user configuration directory")?
+            .join("mimeapps.list");
+
+        let history_path = history::history_path();
+        let history = history_path
+            .as_deref()
+            .map(history::load)
+            .unwrap_or_default();
+
+        let mut manager = Self {
+            user_config_path,
+            document: KeyFile::new(),
+            saved_document: KeyFile::new(),
+            operations: Vec::new(),
+            saved_history: history.clone(),
+            history,
+            history_path,
+            backup_dir: storage::backup_dir(),
+            disk_fingerprint: None,
+            current_defaults: HashMap::new(),
+            added_associations: HashMap::new(),
+            removed_associations: HashMap::new(),
+            layers: Vec::new(),
+        };
+
+        manager.load_current_defaults()?;
+        Ok(manager)
+    }
+
+    pub fn user_config_path(&self) -> &Path {
+        &self.user_config_path
+    }
+
+    /// Every `mimeapps.list` that can affect the defaults, existing or not.
+    pub fn mimeapps_paths(&self) -> Vec<PathBuf> {
+        resolver::mimeapps_paths()
+    }
+
+    /// Reads every `mimeapps.list` again, replaying the staged changes on
+    /// top so those made by other programs are kept as well. When another
+    /// program changed a type with staged changes, nothing is reloaded and a
+    /// [`ConflictError`] is returned, to be settled with
+    /// [`Self::resolve_conflicts`].
+    pub fn reload(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let conflicts = self.find_conflicts()?;
+        if !conflicts.is_empty() {
+            return Err(ConflictError {
+                path: self.user_config_path.clone(),
+                conflicts,
+            }
+            .into());
+        }
+        self.load_current_defaults()
+    }
+
+    /// Reloads after a [`ConflictError`], keeping either the staged version
+    /// of the conflicting types or what the other program wrote. Staged
+    /// changes to other types are replayed either way.
+    pub fn resolve_conflicts(&mut self, keep_mine: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let conflicts = self.find_conflicts()?;
+        if keep_mine {
+            // Put back the whole staged list, not a mix with theirs
+            self.operations
+                .extend(conflicts.into_iter().map(|conflict| Operation::Replace {
+                    mimetype: conflict.mimetype,
+                    kind: conflict.kind,
+                    desktop_files: conflict.new,
+                }));
+        } else {
+            self.operations.retain(|operation| {
+                !conflicts
+                    .iter()
+                    .any(|conflict| operation.touches(&conflict.mimetype, conflict.kind))
+            });
+        }
+        self.load_current_defaults()
+    }
+
+    /// Whether saving would change the user's `mimeapps.list`.
+    pub fn has_pending_changes(&self) -> bool {
+        !self.pending_changes().is_empty()
+    }
+
+    /// What saving would change, per type and group.
+    pub fn pending_changes(&self) -> Vec<PendingChange> {
+        let mut changes: Vec<PendingChange> = CHANGE_KINDS
+            .into_iter()
+            .flat_map(|kind| {
+                let saved = read_list_group(&self.saved_document, group_name(kind));
+                changes_between(kind, &saved, self.lists(kind))
+            })
+            .collect();
+        changes.sort_by(|a, b| a.mimetype.cmp(&b.mimetype));
+        changes
+    }
+
+    /// Applies `update` as a single step of the history.
+    pub fn record(&mut self, description: &str, update: impl FnOnce(&mut Self)) {
+        let before = CHANGE_KINDS.map(|kind| self.lists(kind).clone());
+        update(self);
+
+        let changes: Vec<PendingChange> = CHANGE_KINDS
+            .into_iter()
+            .zip(&before)
+            .flat_map(|(kind, before)| changes_between(kind, before, self.lists(kind)))
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+
+        // What was undone can't be redone once something else changed
+        self.history.retain(|entry| !entry.undone);
+        self.history.push(HistoryEntry::new(description, changes));
+    }
+
+    /// Changes made so far, the oldest first, including those of previous
+    /// sessions.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.iter().any(|entry| !entry.undone)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.iter().any(|entry| entry.undone)
+    }
+
+    /// Stages going back on the latest change still in effect, returning
+    /// its description.
+    pub fn undo(&mut self) -> Option<String> {
+        let index = self.history.iter().rposition(|entry| !entry.undone)?;
+        self.history[index].undone = true;
+        let entry = self.history[index].clone();
+
+        for change in entry.changes {
+            self.stage(Operation::Replace {
+                mimetype: change.mimetype,
+                kind: change.kind,
+                desktop_files: change.old,
+            });
+        }
+        Some(entry.description)
+    }
+
+    /// Stages the earliest undone change again, returning its description.
+    pub fn redo(&mut self) -> Option<String> {
+        let index = self.history.iter().position(|entry| entry.undone)?;
+        self.history[index].undone = false;
+        let entry = self.history[index].clone();
+
+        for change in entry.changes {
+            self.stage(Operation::Replace {
+                mimetype: change.mimetype,
+                kind: change.kind,
+                desktop_files: change.new,
+            });
+        }
+        Some(entry.description)
+    }
+
+    /// Stages putting back what the history entry `id` replaced, as a new
+    /// change. Later changes to the same types are reverted as well.
+    pub fn revert(&mut self, id: u64) {
+        let Some(entry) = self.history.iter().find(|entry| entry.id == id).cloned() else {
+            return;
+        };
+
+        self.record(&format!("Revert “{}”", entry.description), |manager| {
+            for change in entry.changes {
+                manager.stage(Operation::Replace {
+                    mimetype: change.mimetype,
+                    kind: change.kind,
+                    desktop_files: change.old,
+                });
+            }
+        });
+    }
+
+    /// Writes the staged changes to the user's `mimeapps.list`, in one go.
+    pub fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.has_pending_changes() {
+            self.operations.clear();
+            return Ok(());
+        }
+        if self.has_external_changes() {
+            return Err(ExternalChangeError {
+                path: self.user_config_path.clone(),
+            }
+            .into());
+        }
+        self.write_document()
+    }
+
+    /// Drops the staged changes, going back to what the file holds.
+    pub fn discard_changes(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.operations.clear();
+        self.history = self.saved_history.clone();
+        self.load_current_defaults()
+    }
+
+    /// Whether the user's `mimeapps.list` no longer holds what we last read
+    /// or wrote.
+    pub fn has_external_changes(&self) -> bool {
+        storage::read_fingerprint(&self.user_config_path)
+            .is_ok_and(|fingerprint| fingerprint != self.disk_fingerprint)
+    }
+
+    /// Writes our version of the user's `mimeapps.list` even though another
+    /// program changed it, after an [`ExternalChangeError`].
+    pub fn overwrite_external_changes(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_document()
+    }
+
+    /// The application that will really open `mimetype`, taking every
+    /// `mimeapps.list` on the system into account.
+    pub fn get_effective_default(
+        &self,
+        mimetype: &str,
+        is_installed: impl Fn(&str) -> bool,
+    ) -> Option<EffectiveDefault> {
+        resolver::resolve_default(&self.layers, mimetype, is_installed)
+    }
+
+    /// Like [`Self::get_effective_default`], but walks the aliases and parent
+    /// types of `mimetype` when it has no default of its own, the way
+    /// desktops pick the application that opens a file.
+    pub fn get_inherited_default(
+        &self,
+        mimetype: &str,
+        mime_database: &MimeDatabase,
+        is_installed: impl Fn(&str) -> bool,
+    ) -> Option<EffectiveDefault> {
+        resolver::resolve_inherited_default(
+            &self.layers,
+            &mime_database.fallback_chain(mimetype),
+            is_installed,
+        )
+    }
+
+    /// Applications that can open `mimetype`, given the ones declaring it in
+    /// their desktop file, after applying every file's added and removed
+    /// associations.
+    pub fn get_associated_apps(
+        &self,
+        mimetype: &str,
+        declared: impl IntoIterator<Item = String>,
+    ) -> Vec<String> {
+        resolver::resolve_associations(&self.layers, mimetype, declared)
+    }
+
+    pub fn get_default_app(&self, mimetype: &str) -> Option<&String> {
+        self.get_default_apps(mimetype).first()
+    }
+
+    /// The defaults of `mimetype` in the user's file, the preferred one first
+    /// and then the fallbacks to use when it isn't installed.
+    pub fn get_default_apps(&self, mimetype: &str) -> &[String] {
+        self.current_defaults
+            .get(mimetype)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Makes `desktop_file` the preferred application for `mimetype`, keeping
+    /// the previous defaults as fallbacks.
+    pub fn set_default_app(&mut self, mimetype: &str, desktop_file: &str) {
+        self.stage(Operation::SetDefault {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+        });
+    }
+
+    /// Adds `desktop_file` as the last fallback for `mimetype`.
+    pub fn add_fallback_app(&mut self, mimetype: &str, desktop_file: &str) {
+        self.stage(Operation::AddFallback {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+        });
+    }
+
+    /// Moves `desktop_file` to `position` in the defaults of `mimetype`.
+    pub fn move_default_app(&mut self, mimetype: &str, desktop_file: &str, position: usize) {
+        self.stage(Operation::MoveDefault {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+            position,
+        });
+    }
+
+    /// Drops every default of `mimetype` from the user's file.
+    pub fn unset_default_apps(&mut self, mimetype: &str) {
+        self.stage(Operation::UnsetDefaults {
+            mimetype: mimetype.to_string(),
+        });
+    }
+
+    pub fn remove_default_app(&mut self, mimetype: &str, desktop_file: &str) {
+        self.stage(Operation::RemoveDefault {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+        });
+    }
+
+    /// Stages making `desktop_file` the default for each of `mimetypes`.
+    pub fn set_default_app_for_all(&mut self, mimetypes: &[&str], desktop_file: &str) {
+        for mimetype in mimetypes {
+            self.set_default_app(mimetype, desktop_file);
+        }
+    }
+
+    /// Stages making `desktop_file` the default web browser, for links and
+    /// web pages alike.
+    pub fn set_default_browser(&mut self, desktop_file: &str) {
+        self.set_default_app_for_all(WEB_BROWSER_TYPES, desktop_file);
+    }
+
+    /// Linked types, like `http` and `https` links, opened by different
+    /// applications.
+    pub fn get_split_handlers(&self, is_installed: impl Fn(&str) -> bool) -> Vec<SplitHandlers> {
+        LINKED_TYPES
+            .iter()
+            .filter_map(|types| {
+                let defaults: Vec<(&'static str, String)> = types
+                    .mimetypes
+                    .iter()
+                    .filter_map(|&mimetype| {
+                        let default = self.get_effective_default(mimetype, &is_installed)?;
+                        Some((mimetype, default.desktop_file))
+                    })
+                    .collect();
+
+                let first = &defaults.first()?.1;
+                defaults
+                    .iter()
+                    .any(|(_, desktop_file)| desktop_file != first)
+                    .then_some(SplitHandlers { types, defaults })
+            })
+            .collect()
+    }
+
+    /// Apps listed in `[Added Associations]` for `mimetype`, in preference order.
+    pub fn get_added_associations(&self, mimetype: &str) -> &[String] {
+        self.added_associations
+            .get(mimetype)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Apps listed in `[Removed Associations]` for `mimetype`.
+    pub fn get_removed_associations(&self, mimetype: &str) -> &[String] {
+        self.removed_associations
+            .get(mimetype)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Makes `desktop_file` a handler of `mimetype`, even if it doesn't declare it.
+    pub fn add_association(&mut self, mimetype: &str, desktop_file: &str) {
+        self.stage(Operation::AddAssociation {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+        });
+    }
+
+    /// Hides `desktop_file` from the handlers of `mimetype`.
+    pub fn remove_association(&mut self, mimetype: &str, desktop_file: &str) {
+        self.stage(Operation::RemoveAssociation {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+        });
+    }
+
+    /// Drops any added or removed association between `desktop_file` and
+    /// `mimetype`, going back to what the application itself declares.
+    pub fn reset_association(&mut self, mimetype: &str, desktop_file: &str) {
+        self.stage(Operation::ResetAssociation {
+            mimetype: mimetype.to_string(),
+            desktop_file: desktop_file.to_string(),
+        });
+    }
+
+    pub fn get_all_mimetypes(&self) -> Vec<String> {
+        let mimetypes: BTreeSet<&String> = self
+            .layers
+            .iter()
+            .flat_map(|layer| layer.default_mimetypes())
+            .collect();
+        mimetypes.into_iter().cloned().collect()
+    }
+
+    /// Types the user's `mimeapps.list` associates with `desktop_file`, as a
+    /// default or an added association.
+    pub fn get_mimetypes_for_app(&self, desktop_file: &str) -> Vec<String> {
+        let mimetypes: BTreeSet<&String> = self
+            .current_defaults
+            .iter()
+            .chain(&self.added_associations)
+            .filter(|(_, apps)| apps.iter().any(|app| app == desktop_file))
+            .map(|(mimetype, _)| mimetype)
+            .collect();
+        mimetypes.into_iter().cloned().collect()
+    }
+
+    /// Backups of the user's `mimeapps.list`, the newest first.
+    pub fn list_backups(&self) -> Vec<Backup> {
+        self.backup_dir
+            .as_deref()
+            .map(storage::list_backups)
+            .unwrap_or_default()
+    }
+
+    /// Replaces the user's `mimeapps.list` with `backup`, backing up the
+    /// current contents first so the restore can be undone. Staged changes
+    /// are dropped.
+    pub fn restore_backup(&mut self, backup: &Backup) -> Result<(), Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(&backup.path)?;
+        self.operations.clear();
+        self.history = self.saved_history.clone();
+
+        if let Some(backup_dir) = &self.backup_dir {
+            storage::create_backup(&self.user_config_path, backup_dir)?;
+        }
+        storage::write_atomically(&self.user_config_path, &content)?;
+
+        self.load_current_defaults()
+    }
+
+    // The user's `mimeapps.list`, `None` if there's none yet
+    fn read_user_config(&self) -> std::io::Result<Option<String>> {
+        match fs::read_to_string(&self.user_config_path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Types another program changed since the file was read, which staged
+    // changes are about too
+    fn find_conflicts(&self) -> Result<Vec<PendingChange>, Box<dyn std::error::Error>> {
+        if self.operations.is_empty() || !self.has_external_changes() {
+            return Ok(Vec::new());
+        }
+        let disk = KeyFile::parse(self.read_user_config()?.as_deref().unwrap_or_default());
+
+        let mut conflicts = Vec::new();
+        for kind in CHANGE_KINDS {
+            let saved = read_list_group(&self.saved_document, group_name(kind));
+            let theirs = read_list_group(&disk, group_name(kind));
+            for change in changes_between(kind, &saved, &theirs) {
+                let mine = self
+                    .lists(kind)
+                    .get(&change.mimetype)
+                    .cloned()
+                    .unwrap_or_default();
+                // Making the same change isn't a conflict
+                if mine != change.new
+                    && self
+                        .operations
+                        .iter()
+                        .any(|operation| operation.touches(&change.mimetype, kind))
+                {
+                    conflicts.push(PendingChange {
+                        old: change.new,
+                        new: mine,
+                        ..change
+                    });
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+
+    fn load_current_defaults(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.layers = resolver::load_layers();
+
+        let content = self.read_user_config()?;
+        self.disk_fingerprint = content
+            .as_ref()
+            .map(|content| storage::fingerprint(content.as_bytes()));
+        self.document = KeyFile::parse(content.as_deref().unwrap_or_default());
+        self.saved_document = self.document.clone();
+
+        self.current_defaults = read_list_group(&self.document, DEFAULT_APPLICATIONS);
+        self.added_associations = read_list_group(&self.document, ADDED_ASSOCIATIONS);
+        self.removed_associations = read_list_group(&self.document, REMOVED_ASSOCIATIONS);
+
+        // Staged changes go on top of what's on disk now
+        let operations = std::mem::take(&mut self.operations);
+        for operation in &operations {
+            self.apply_operation(operation);
+        }
+        self.operations = operations;
+        self.sync_document();
+        self.refresh_user_layer();
+
+        Ok(())
+    }
+
+    fn stage(&mut self, operation: Operation) {
+        self.apply_operation(&operation);
+        self.operations.push(operation);
+        self.sync_document();
+        self.refresh_user_layer();
+    }
+
+    fn apply_operation(&mut self, operation: &Operation) {
+        match operation {
+            Operation::SetDefault {
+                mimetype,
+                desktop_file,
+            } => {
+                let apps = self.current_defaults.entry(mimetype.clone()).or_default();
+                apps.retain(|app| app != desktop_file);
+                apps.insert(0, desktop_file.clone());
+            }
+            Operation::AddFallback {
+                mimetype,
+                desktop_file,
+            } => add_to_list(&mut self.current_defaults, mimetype, desktop_file),
+            Operation::MoveDefault {
+                mimetype,
+                desktop_file,
+                position,
+            } => {
+                if let Some(apps) = self.current_defaults.get_mut(mimetype)
+                    && let Some(current) = apps.iter().position(|app| app == desktop_file)
+                {
+                    let app = apps.remove(current);
+                    apps.insert((*position).min(apps.len()), app);
+                }
+            }
+            Operation::UnsetDefaults { mimetype } => {
+                self.current_defaults.remove(mimetype);
+            }
+            Operation::RemoveDefault {
+                mimetype,
+                desktop_file,
+            } => remove_from_list(&mut self.current_defaults, mimetype, desktop_file),
+            Operation::AddAssociation {
+                mimetype,
+                desktop_file,
+            } => {
+                remove_from_list(&mut self.removed_associations, mimetype, desktop_file);
+                add_to_list(&mut self.added_associations, mimetype, desktop_file);
+            }
+            Operation::RemoveAssociation {
+                mimetype,
+                desktop_file,
+            } => {
+                remove_from_list(&mut self.added_associations, mimetype, desktop_file);
+                add_to_list(&mut self.removed_associations, mimetype, desktop_file);
+            }
+            Operation::ResetAssociation {
+                mimetype,
+                desktop_file,
+            } => {
+                remove_from_list(&mut self.added_associations, mimetype, desktop_file);
+                remove_from_list(&mut self.removed_associations, mimetype, desktop_file);
+            }
+            Operation::Replace {
+                mimetype,
+                kind,
+                desktop_files,
+            } => {
+                let lists = self.lists_mut(*kind);
+                if desktop_files.is_empty() {
+                    lists.remove(mimetype);
+                } else {
+                    lists.insert(mimetype.clone(), desktop_files.clone());
+                }
+            }
+        }
+    }
+
+    fn lists(&self, kind: ChangeKind) -> &HashMap<String, Vec<String>> {
+        match kind {
+            ChangeKind::Defaults => &self.current_defaults,
+            ChangeKind::AddedAssociations => &self.added_associations,
+            ChangeKind::RemovedAssociations => &self.removed_associations,
+        }
+    }
+
+    fn lists_mut(&mut self, kind: ChangeKind) -> &mut HashMap<String, Vec<String>> {
+        match kind {
+            ChangeKind::Defaults => &mut self.current_defaults,
+            ChangeKind::AddedAssociations => &mut self.added_associations,
+            ChangeKind::RemovedAssociations => &mut self.removed_associations,
+        }
+    }
+
+    // Keep the user's layer in sync with the edited document
+    fn refresh_user_layer(&mut self) {
+        let layer = MimeappsLayer::from_keyfile(&self.user_config_path, &self.document);
+
+        if let Some(existing) = self
+            .layers
+            .iter_mut()
+            .find(|layer| layer.path == self.user_config_path)
+        {
+            *existing = layer;
+            return;
+        }
+
+        // The file didn't exist yet, slot it in by precedence
+        let paths = resolver::mimeapps_paths();
+        let rank = |path: &Path| paths.iter().position(|p| p == path);
+        let user_rank = rank(&self.user_config_path);
+        let index = self
+            .layers
+            .iter()
+            .position(|existing| rank(&existing.path) > user_rank)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(index, layer);
+    }
+
+    fn sync_document(&mut self) {
+        // Only touch the keys that changed, everything else is written back as read
+        sync_group(
+            &mut self.document,
+            DEFAULT_APPLICATIONS,
+            &join_list_group(&self.current_defaults),
+        );
+        sync_group(
+            &mut self.document,
+            ADDED_ASSOCIATIONS,
+            &join_list_group(&self.added_associations),
+        );
+        sync_group(
+            &mut self.document,
+            REMOVED_ASSOCIATIONS,
+            &join_list_group(&self.removed_associations),
+        );
+    }
+
+    fn write_document(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // Keep the previous version around, then swap the file in one go
+        if let Some(backup_dir) = &self.backup_dir {
+            storage::create_backup(&self.user_config_path, backup_dir)?;
+        }
+        let content = self.document.to_string();
+        storage::write_atomically(&self.user_config_path, &content)?;
+        self.disk_fingerprint = Some(storage::fingerprint(content.as_bytes()));
+        self.saved_document = self.document.clone();
+        self.operations.clear();
+        self.refresh_user_layer();
+
+        // The journal only holds changes that were saved
+        self.saved_history = self.history.clone();
+        if let Some(history_path) = &self.history_path
+            && let Err(e) = history::save(history_path, &self.history)
+        {
+            eprintln!("Failed to save the history: {}", e);
+        }
+
+        Ok(())
+    }
+}
+
+fn group_name(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::Defaults => DEFAULT_APPLICATIONS,
+        ChangeKind::AddedAssociations => ADDED_ASSOCIATIONS,
+        ChangeKind::RemovedAssociations => REMOVED_ASSOCIATIONS,
+    }
+}
+
+/// The types whose list differs between `old` and `new`.
+fn changes_between(
+    kind: ChangeKind,
+    old: &HashMap<String, Vec<String>>,
+    new: &HashMap<String, Vec<String>>,
+) -> Vec<PendingChange> {
+    let mimetypes: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    mimetypes
+        .into_iter()
+        .filter_map(|mimetype| {
+            let old = old.get(mimetype).cloned().unwrap_or_default();
+            let new = new.get(mimetype).cloned().unwrap_or_default();
+            (old != new).then(|| PendingChange {
+                mimetype: mimetype.clone(),
+                kind,
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+/// Splits a `;`-separated list of desktop file IDs.
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn join_list(items: &[String]) -> String {
+    items.iter().map(|item| format!("{};", item)).collect()
+}
+
+fn read_list_group(document: &KeyFile, group: &str) -> HashMap<String, Vec<String>> {
+    document
+        .entries(group)
+        .into_iter()
+        .map(|(mimetype, value)| (mimetype.to_string(), parse_list(value)))
+        .filter(|(_, apps)| !apps.is_empty())
+        .collect()
+}
+
+fn join_list_group(lists: &HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    lists
+        .iter()
+        .filter(|(_, apps)| !apps.is_empty())
+        .map(|(mimetype, apps)| (mimetype.clone(), join_list(apps)))
+        .collect()
+}
+
+fn add_to_list(lists: &mut HashMap<String, Vec<String>>, mimetype: &str, desktop_file: &str) {
+    let apps = lists.entry(mimetype.to_string()).or_default();
+    if !apps.iter().any(|app| app == desktop_file) {
+        apps.push(desktop_file.to_string());
+    }
+}
+
+fn remove_from_list(lists: &mut HashMap<String, Vec<String>>, mimetype: &str, desktop_file: &str) {
+    if let Some(apps) = lists.get_mut(mimetype) {
+        apps.retain(|app| app != desktop_file);
+        if apps.is_empty() {
+            lists.remove(mimetype);
+        }
+    }
+}
+
+/// Makes `group` in `document` hold exactly `values`, leaving the lines of
+/// unchanged keys (including their formatting) alone.
+fn sync_group(document: &mut KeyFile, group: &str, values: &HashMap<String, String>) {
+    // Keys listing no application (`foo/bar=`) were never in the lists, they
+    // stay as they are
+    let stale: Vec<String> = document
+        .entries(group)
+        .into_iter()
+        .filter(|(key, value)| !values.contains_key(*key) && !parse_list(value).is_empty())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in stale {
+        document.remove(group, &key);
+    }
+
+    let mut keys: Vec<&String> = values.keys().collect();
+    keys.sort();
+    for key in keys {
+        // `a.desktop` and `a.desktop;` mean the same, keep whatever is there
+        let unchanged = document
+            .get(group, key)
+            .is_some_and(|current| parse_list(current) == parse_list(&values[key]));
+        if !unchanged {
+            document.set(group, key, &values[key]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A manager of `content`, in a file of its own
+    fn manager(name: &str, content: &str) -> MimetypeManager {
+        let path =
+            std::env::temp_dir().join(format!("pick_def_app-{}-{}.list", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+
+        let mut manager = MimetypeManager {
+            user_config_path: path,
+            document: KeyFile::new(),
+            saved_document: KeyFile::new(),
+            operations: Vec::new(),
+            history: Vec::new(),
+            saved_history: Vec::new(),
+            history_path: None,
+            backup_dir: None,
+            disk_fingerprint: None,
+            current_defaults: HashMap::new(),
+            added_associations: HashMap::new(),
+            removed_associations: HashMap::new(),
+            layers: Vec::new(),
+        };
+        manager.load_current_defaults().unwrap();
+        manager
+    }
+
+    #[test]
+    fn reload_refuses_to_replay_over_external_edits() {
+        let mut manager = manager(
+            "conflict",
+            "[Default Applications]\ntext/plain=a.desktop;\nimage/png=b.desktop;\n",
+        );
+        manager.set_default_app("text/plain", "mine.desktop");
+        fs::write(
+            &manager.user_config_path,
+            "[Default Applications]\ntext/plain=theirs.desktop;\nimage/png=c.desktop;\n",
+        )
+        .unwrap();
+
+        let error = manager.reload().unwrap_err();
+        let conflicts = &error.downcast_ref::<ConflictError>().unwrap().conflicts;
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].mimetype, "text/plain");
+        assert_eq!(conflicts[0].old, ["theirs.desktop"]);
+        assert_eq!(conflicts[0].new, ["mine.desktop", "a.desktop"]);
+        // Nothing was reloaded, so saving is still refused
+        assert!(manager.has_external_changes());
+        assert!(manager.save().is_err());
+
+        manager.resolve_conflicts(true).unwrap();
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["mine.desktop", "a.desktop"]
+        );
+        assert_eq!(manager.get_default_apps("image/png"), ["c.desktop"]);
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn conflicts_can_be_settled_with_the_other_version() {
+        let mut manager = manager("theirs", "[Default Applications]\ntext/plain=a.desktop;\n");
+        manager.set_default_app("text/plain", "mine.desktop");
+        manager.set_default_app("image/png", "mine.desktop");
+        fs::write(
+            &manager.user_config_path,
+            "[Default Applications]\ntext/plain=theirs.desktop;\n",
+        )
+        .unwrap();
+
+        manager.resolve_conflicts(false).unwrap();
+        assert_eq!(manager.get_default_apps("text/plain"), ["theirs.desktop"]);
+        assert_eq!(manager.get_default_apps("image/png"), ["mine.desktop"]);
+        assert!(!manager.has_external_changes());
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn unrelated_external_edits_are_merged() {
+        let mut manager = manager("merge", "[Default Applications]\ntext/plain=a.desktop;\n");
+        manager.set_default_app("text/plain", "mine.desktop");
+        fs::write(
+            &manager.user_config_path,
+            "[Default Applications]\ntext/plain=a.desktop;\nimage/png=c.desktop;\n",
+        )
+        .unwrap();
+
+        manager.reload().unwrap();
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["mine.desktop", "a.desktop"]
+        );
+        assert_eq!(manager.get_default_apps("image/png"), ["c.desktop"]);
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn saving_over_external_edits_needs_overwriting() {
+        let mut manager = manager(
+            "overwrite",
+            "[Default Applications]\ntext/plain=a.desktop;\n",
+        );
+        manager.set_default_app("text/plain", "mine.desktop");
+        let theirs = "[Default Applications]\ntext/plain=theirs.desktop;\n";
+        fs::write(&manager.user_config_path, theirs).unwrap();
+
+        let error = manager.save().unwrap_err();
+        let error = error.downcast_ref::<ExternalChangeError>().unwrap();
+        assert_eq!(error.path, manager.user_config_path);
+        assert_eq!(
+            fs::read_to_string(&manager.user_config_path).unwrap(),
+            theirs
+        );
+        assert!(manager.has_pending_changes());
+
+        manager.overwrite_external_changes().unwrap();
+        let saved = fs::read_to_string(&manager.user_config_path).unwrap();
+        assert!(saved.contains("text/plain=mine.desktop;a.desktop;"));
+        assert!(!manager.has_pending_changes());
+        assert!(!manager.has_external_changes());
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn undone_changes_can_be_redone() {
+        let mut manager = manager("redo", "[Default Applications]\ntext/plain=a.desktop;\n");
+        manager.record("Set b", |manager| {
+            manager.set_default_app("text/plain", "b.desktop")
+        });
+
+        assert_eq!(manager.undo().as_deref(), Some("Set b"));
+        assert_eq!(manager.get_default_apps("text/plain"), ["a.desktop"]);
+        assert!(!manager.can_undo());
+        assert!(manager.can_redo());
+
+        assert_eq!(manager.redo().as_deref(), Some("Set b"));
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["b.desktop", "a.desktop"]
+        );
+        assert!(manager.can_undo());
+        assert!(!manager.can_redo());
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn new_changes_drop_undone_ones() {
+        let mut manager = manager(
+            "drop-redo",
+            "[Default Applications]\ntext/plain=a.desktop;\n",
+        );
+        manager.record("Set b", |manager| {
+            manager.set_default_app("text/plain", "b.desktop")
+        });
+        manager.undo();
+        manager.record("Set c", |manager| {
+            manager.set_default_app("image/png", "c.desktop")
+        });
+
+        assert!(!manager.can_redo());
+        assert_eq!(manager.redo(), None);
+        let descriptions: Vec<&str> = manager
+            .history()
+            .iter()
+            .map(|entry| entry.description.as_str())
+            .collect();
+        assert_eq!(descriptions, ["Set c"]);
+        assert_eq!(manager.get_default_apps("text/plain"), ["a.desktop"]);
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn reverting_finds_the_entry_after_the_history_changed() {
+        let mut manager = manager("revert", "[Default Applications]\ntext/plain=a.desktop;\n");
+        manager.record("Set b", |manager| {
+            manager.set_default_app("text/plain", "b.desktop")
+        });
+        manager.record("Set c", |manager| {
+            manager.set_default_app("image/png", "c.desktop")
+        });
+        let id = manager.history()[1].id;
+
+        // As when the oldest entries are dropped
+        manager.history.remove(0);
+        manager.revert(id);
+
+        assert!(manager.get_default_apps("image/png").is_empty());
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["b.desktop", "a.desktop"]
+        );
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn saving_only_rewrites_changed_lines() {
+        let content = "# Managed by hand\n\
+                       [Default Applications]\n\
+                       text/plain = a.desktop\n\
+                       \n\
+                       # Between groups\n\
+                       [Added Associations]\n\
+                       image/png=b.desktop;\n\
+                       \n\
+                       [Default Applications]\n\
+                       image/png=c.desktop;\n\
+                       foo/bar=\n";
+        let mut manager = manager("save", content);
+        manager.set_default_app("image/png", "d.desktop");
+        manager.save().unwrap();
+
+        let saved = fs::read_to_string(&manager.user_config_path).unwrap();
+        fs::remove_file(&manager.user_config_path).unwrap();
+        // The last of the duplicate groups is the one readers use
+        assert_eq!(
+            saved,
+            content.replace("image/png=c.desktop;", "image/png=d.desktop;c.desktop;")
+        );
+    }
+
+    #[test]
+    fn associations_move_between_added_and_removed() {
+        let mut manager = manager("associations", "");
+        manager.add_association("image/png", "a.desktop");
+        manager.add_association("image/png", "b.desktop");
+        manager.add_association("image/png", "a.desktop");
+        assert_eq!(
+            manager.get_added_associations("image/png"),
+            ["a.desktop", "b.desktop"]
+        );
+
+        manager.remove_association("image/png", "a.desktop");
+        assert_eq!(manager.get_added_associations("image/png"), ["b.desktop"]);
+        assert_eq!(manager.get_removed_associations("image/png"), ["a.desktop"]);
+
+        manager.add_association("image/png", "a.desktop");
+        assert!(manager.get_removed_associations("image/png").is_empty());
+
+        manager.reset_association("image/png", "a.desktop");
+        manager.reset_association("image/png", "b.desktop");
+        assert!(manager.get_added_associations("image/png").is_empty());
+        assert!(!manager.has_pending_changes());
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn defaults_keep_their_order() {
+        let mut manager = manager(
+            "ordering",
+            "[Default Applications]\ntext/plain=a.desktop;b.desktop;\n",
+        );
+        manager.add_fallback_app("text/plain", "c.desktop");
+        manager.add_fallback_app("text/plain", "a.desktop");
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["a.desktop", "b.desktop", "c.desktop"]
+        );
+
+        // Setting a default puts it first, without listing it twice
+        manager.set_default_app("text/plain", "b.desktop");
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["b.desktop", "a.desktop", "c.desktop"]
+        );
+
+        manager.move_default_app("text/plain", "b.desktop", 2);
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["a.desktop", "c.desktop", "b.desktop"]
+        );
+        manager.move_default_app("text/plain", "c.desktop", 10);
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["a.desktop", "b.desktop", "c.desktop"]
+        );
+        manager.move_default_app("text/plain", "missing.desktop", 0);
+        assert_eq!(manager.get_default_app("text/plain").unwrap(), "a.desktop");
+
+        manager.remove_default_app("text/plain", "a.desktop");
+        assert_eq!(
+            manager.get_default_apps("text/plain"),
+            ["b.desktop", "c.desktop"]
+        );
+        manager.unset_default_apps("text/plain");
+        assert!(manager.get_default_apps("text/plain").is_empty());
+        fs::remove_file(&manager.user_config_path).unwrap();
+    }
+
+    #[test]
+    fn sync_group_keeps_untouched_empty_keys() {
+        let content =
+            "[Default Applications]\nfoo/bar=\ntext/plain=a.desktop;\nimage/png=b.desktop\n";
+        let mut document = KeyFile::parse(content);
+
+        let mut lists = read_list_group(&document, DEFAULT_APPLICATIONS);
+        lists.remove("text/plain");
+        sync_group(
+            &mut document,
+            DEFAULT_APPLICATIONS,
+            &join_list_group(&lists),
+        );
+
+        assert_eq!(
+            document.to_string(),
+            "[Default Applications]\nfoo/bar=\nimage/png=b.desktop\n"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use super::{
+    ADDED_ASSOCIATIONS, DEFAULT_APPLICATIONS, KeyFile, REMOVED_ASSOCIATIONS, read_list_group,
+};
+use crate::xdg_dirs;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The default application of a MIME type, and the file that set it.
+#[derive(Debug, Clone)]
+pub struct EffectiveDefault {
+    pub desktop_file: String,
+    pub source: PathBuf,
+    // The type the default was set for: an alias or a parent type when it
+    // was inherited
+    pub mimetype: String,
+}
+
+/// The associations read from one `mimeapps.list` file.
+#[derive(Debug, Clone)]
+pub struct MimeappsLayer {
+    pub path: PathBuf,
+    defaults: HashMap<String, Vec<String>>,
+    added: HashMap<String, Vec<String>>,
+    removed: HashMap<String, Vec<String>>,
+}
+
+impl MimeappsLayer {
+    pub fn from_keyfile(path: &Path, document: &KeyFile) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            defaults: read_list_group(document, DEFAULT_APPLICATIONS),
+            added: read_list_group(document, ADDED_ASSOCIATIONS),
+            removed: read_list_group(document, REMOVED_ASSOCIATIONS),
+        }
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        Some(Self::from_keyfile(path, &KeyFile::parse(&content)))
+    }
+
+    fn list<'a>(lists: &'a HashMap<String, Vec<String>>, mimetype: &str) -> &'a [String] {
+        lists.get(mimetype).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn defaults(&self, mimetype: &str) -> &[String] {
+        Self::list(&self.defaults, mimetype)
+    }
+
+    pub fn added(&self, mimetype: &str) -> &[String] {
+        Self::list(&self.added, mimetype)
+    }
+
+    pub fn removed(&self, mimetype: &str) -> &[String] {
+        Self::list(&self.removed, mimetype)
+    }
+
+    pub fn default_mimetypes(&self) -> impl Iterator<Item = &String> {
+        self.defaults.keys()
+    }
+}
+
+/// All `mimeapps.list` locations from the MIME Applications spec, from the
+/// highest precedence to the lowest.
+pub fn mimeapps_paths() -> Vec<PathBuf> {
+    let desktops = xdg_dirs::current_desktops();
+
+    let mut dirs = Vec::new();
+    dirs.extend(xdg_dirs::config_home());
+    dirs.extend(xdg_dirs::config_dirs());
+    dirs.extend(xdg_dirs::data_home().map(|dir| dir.join("applications")));
+    dirs.extend(
+        xdg_dirs::data_dirs()
+            .into_iter()
+            .map(|dir| dir.join("applications")),
+    );
+
+    let mut paths = Vec::new();
+    for dir in dirs {
+        for desktop in &desktops {
+            paths.push(dir.join(format!("{}-mimeapps.list", desktop)));
+        }
+        paths.push(dir.join("mimeapps.list"));
+    }
+
+    // A directory can be listed twice, e.g. $XDG_CONFIG_HOME in $XDG_CONFIG_DIRS
+    let mut unique = Vec::new();
+    for path in paths {
+        if !unique.contains(&path) {
+            unique.push(path);
+        }
+    }
+    unique
+}
+
+/// Loads every existing `mimeapps.list`, keeping the precedence order.
+pub fn load_layers() -> Vec<MimeappsLayer> {
+    mimeapps_paths()
+        .iter()
+        .filter_map(|path| MimeappsLayer::load(path))
+        .collect()
+}
+
+/// Finds the default application of `mimetype` across `layers`.
+///
+/// The default is the first installed application listed in the highest
+/// precedence `[Default Applications]`, unless a `[Removed Associations]` of
+/// higher or equal precedence hides it. Without one, the first usable
+/// `[Added Associations]` entry is used, like desktops do.
+pub fn resolve_default(
+    layers: &[MimeappsLayer],
+    mimetype: &str,
+    is_installed: impl Fn(&str) -> bool,
+) -> Option<EffectiveDefault> {
+    let find = |list: for<'a> fn(&'a MimeappsLayer, &str) -> &'a [String]| {
+        let mut removed: Vec<&str> = Vec::new();
+        for layer in layers {
+            removed.extend(layer.removed(mimetype).iter().map(String::as_str));
+
+            if let Some(app) = list(layer, mimetype)
+                .iter()
+                .find(|app| !removed.contains(&app.as_str()) && is_installed(app))
+            {
+                return Some(EffectiveDefault {
+                    desktop_file: app.clone(),
+                    source: layer.path.clone(),
+                    mimetype: mimetype.to_string(),
+                });
+            }
+        }
+        None
+    };
+
+    find(MimeappsLayer::defaults).or_else(|| find(MimeappsLayer::added))
+}
+
+/// Finds the default of the first type in `chain` that has one, so a type
+/// without its own default inherits the one of an alias or a parent type.
+pub fn resolve_inherited_default(
+    layers: &[MimeappsLayer],
+    chain: &[String],
+    is_installed: impl Fn(&str) -> bool,
+) -> Option<EffectiveDefault> {
+    chain
+        .iter()
+        .find_map(|mimetype| resolve_default(layers, mimetype, &is_installed))
+}
+
+/// Applications offered for `mimetype`: added associations in precedence
+/// order, then the applications declaring it, minus removed associations.
+pub fn resolve_associations(
+    layers: &[MimeappsLayer],
+    mimetype: &str,
+    declared: impl IntoIterator<Item = String>,
+) -> Vec<String> {
+    let mut apps: Vec<String> = Vec::new();
+    let mut removed: Vec<&str> = Vec::new();
+
+    for layer in layers {
+        removed.extend(layer.removed(mimetype).iter().map(String::as_str));
+        for app in layer.added(mimetype) {
+            if !removed.contains(&app.as_str()) && !apps.contains(app) {
+                apps.push(app.clone());
+            }
+        }
+    }
+
+    for app in declared {
+        if !removed.contains(&app.as_str()) && !apps.contains(&app) {
+            apps.push(app);
+        }
+    }
+
+    apps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(path: &str, content: &str) -> MimeappsLayer {
+        MimeappsLayer::from_keyfile(Path::new(path), &KeyFile::parse(content))
+    }
+
+    // The user's list, a desktop's list and the system one, in precedence order
+    fn with_user_list(user: &str) -> Vec<MimeappsLayer> {
+        vec![
+            layer("/home/user/.config/mimeapps.list", user),
+            layer(
+                "/etc/xdg/gnome-mimeapps.list",
+                "[Default Applications]\ntext/plain=gedit.desktop\n",
+            ),
+            layer(
+                "/usr/share/applications/mimeapps.list",
+                "[Default Applications]\ntext/plain=vim.desktop\n\
+                 [Added Associations]\ntext/plain=kate.desktop;\n",
+            ),
+        ]
+    }
+
+    #[test]
+    fn removed_associations_hide_system_defaults() {
+        let layers = with_user_list("[Removed Associations]\ntext/plain=gedit.desktop;\n");
+        let default = resolve_default(&layers, "text/plain", |_| true).unwrap();
+        assert_eq!(default.desktop_file, "vim.desktop");
+        assert_eq!(
+            default.source,
+            Path::new("/usr/share/applications/mimeapps.list")
+        );
+
+        let layers =
+            with_user_list("[Removed Associations]\ntext/plain=gedit.desktop;vim.desktop;\n");
+        let default = resolve_default(&layers, "text/plain", |_| true).unwrap();
+        assert_eq!(default.desktop_file, "kate.desktop");
+        assert_eq!(
+            resolve_associations(&layers, "text/plain", ["vim.desktop".to_string()]),
+            ["kate.desktop"]
+        );
+    }
+
+    #[test]
+    fn lower_removals_dont_hide_higher_defaults() {
+        let layers = vec![
+            layer(
+                "/home/user/.config/mimeapps.list",
+                "[Default Applications]\ntext/plain=gedit.desktop\n",
+            ),
+            layer(
+                "/usr/share/applications/mimeapps.list",
+                "[Removed Associations]\ntext/plain=gedit.desktop\n",
+            ),
+        ];
+        let default = resolve_default(&layers, "text/plain", |_| true).unwrap();
+        assert_eq!(default.desktop_file, "gedit.desktop");
+    }
+}
@@ -0,0 +1,96 @@
+// URL schemes, whose handlers are set as `x-scheme-handler/<scheme>` types
+
+const SCHEME_PREFIX: &str = "x-scheme-handler/";
+
+/// What a web browser opens, set all at once when picking one.
+pub const WEB_BROWSER_TYPES: &[&str] = &[
+    "x-scheme-handler/http",
+    "x-scheme-handler/https",
+    "text/html",
+    "application/xhtml+xml",
+];
+
+/// Types expected to open in the same application, which is confusing when
+/// they don't.
+#[derive(Debug)]
+pub struct LinkedTypes {
+    pub description: &'static str,
+    pub mimetypes: &'static [&'static str],
+}
+
+pub const LINKED_TYPES: &[LinkedTypes] = &[
+    LinkedTypes {
+        description: "Web pages",
+        mimetypes: WEB_BROWSER_TYPES,
+    },
+    LinkedTypes {
+        description: "Phone calls",
+        mimetypes: &["x-scheme-handler/tel", "x-scheme-handler/callto"],
+    },
+    LinkedTypes {
+        description: "IRC chats",
+        mimetypes: &["x-scheme-handler/irc", "x-scheme-handler/ircs"],
+    },
+    LinkedTypes {
+        description: "Zoom meetings",
+        mimetypes: &["x-scheme-handler/zoommtg", "x-scheme-handler/zoomus"],
+    },
+];
+
+/// Linked types opened by different applications.
+#[derive(Debug)]
+pub struct SplitHandlers {
+    pub types: &'static LinkedTypes,
+    // Each type with a default, and the desktop file ID opening it
+    pub defaults: Vec<(&'static str, String)>,
+}
+
+/// The scheme `mimetype` is the handler of, e.g. `mailto` for
+/// `x-scheme-handler/mailto`.
+pub fn scheme(mimetype: &str) -> Option<&str> {
+    mimetype
+        .strip_prefix(SCHEME_PREFIX)
+        .filter(|scheme| !scheme.is_empty())
+}
+
+/// What links with this scheme are, for people (e.g. "Email Addresses" for
+/// `mailto`).
+pub fn scheme_name(scheme: &str) -> String {
+    let name = match scheme.to_lowercase().as_str() {
+        "http" => "Web Pages",
+        "https" => "Secure Web Pages",
+        "mailto" => "Email Addresses",
+        "tel" => "Phone Numbers",
+        "callto" => "Phone Calls",
+        "sms" => "Text Messages",
+        "sip" => "Internet Calls (SIP)",
+        "irc" => "IRC Chats",
+        "ircs" => "Secure IRC Chats",
+        "xmpp" => "XMPP Chats",
+        "matrix" => "Matrix Rooms",
+        "ftp" => "FTP Servers",
+        "sftp" => "SFTP Servers",
+        "ssh" => "SSH Connections",
+        "smb" => "Windows Shares",
+        "magnet" => "Torrent Magnet Links",
+        "webcal" => "Calendar Subscriptions",
+        "geo" => "Map Locations",
+        "feed" => "News Feeds",
+        "git" => "Git Repositories",
+        "appstream" => "Software Center Pages",
+        "steam" => "Steam",
+        "zoommtg" | "zoomus" => "Zoom Meetings",
+        "msteams" => "Microsoft Teams",
+        "slack" => "Slack",
+        "discord" => "Discord",
+        "tg" => "Telegram",
+        "sgnl" => "Signal",
+        "element" => "Element",
+        "spotify" => "Spotify",
+        "vscode" => "Visual Studio Code",
+        "vscodium" => "VSCodium",
+        "obsidian" => "Obsidian",
+        _ => return format!("{}: Links", scheme),
+    };
+    name.to_string()
+}
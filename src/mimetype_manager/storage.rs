@@ -0,0 +1,321 @@
+use crate::xdg_dirs;
+use gtk::glib;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const MAX_BACKUPS: usize = 10;
+
+/// A saved copy of `mimeapps.list`, taken right before it was overwritten.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    // Local time the backup was taken, formatted for display
+    pub created: String,
+}
+
+pub fn backup_dir() -> Option<PathBuf> {
+    Some(xdg_dirs::state_home()?.join("pick_def_app").join("backups"))
+}
+
+/// A cheap summary of a file's contents, to notice when another program
+/// changed it.
+pub fn fingerprint(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The fingerprint of what's in `path` now, `None` if it doesn't exist.
+pub fn read_fingerprint(path: &Path) -> io::Result<Option<u64>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(fingerprint(&content))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Follows symlinks (dangling ones included), so writes land on the real file
+/// and a `mimeapps.list` symlinked from a dotfiles repo stays a symlink.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut current = path.to_path_buf();
+
+    // Same limit as the kernel, in case of loops
+    for _ in 0..40 {
+        match fs::read_link(&current) {
+            Ok(target) => {
+                current = match current.parent() {
+                    Some(parent) => parent.join(target),
+                    None => target,
+                };
+            }
+            Err(_) => break,
+        }
+    }
+
+    current
+}
+
+/// Replaces the contents of `path` without ever leaving it truncated: the new
+/// contents go to a temporary file in the same directory, which is synced
+/// and then renamed over the original.
+pub fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+    let target = resolve_symlinks(path);
+    let dir = target
+        .parent()
+        .ok_or_else(|| io::Error::other("The file has no parent directory"))?;
+    fs::create_dir_all(dir)?;
+
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp_path)?;
+
+        // Keep the permissions of the file we replace
+        if let Ok(metadata) = fs::metadata(&target) {
+            file.set_permissions(metadata.permissions())?;
+        }
+
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &target)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Make the rename itself durable
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Copies the current contents of `path` into `backup_dir`, dropping the
+/// oldest backups beyond the limit.
+pub fn create_backup(path: &Path, backup_dir: &Path) -> io::Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let backups = list_backups(backup_dir);
+
+    // Saving the same state twice in a row doesn't need another backup
+    if let Some(latest) = backups.first()
+        && fs::read_to_string(&latest.path).is_ok_and(|latest| latest == content)
+    {
+        return Ok(());
+    }
+
+    let stamp = glib::DateTime::now_local()
+        .and_then(|now| now.format("%Y%m%d-%H%M%S"))
+        .map_err(io::Error::other)?;
+
+    // Several saves can happen within the same second. Count on from the
+    // newest of them, a name freed by rotation would sort as the oldest.
+    let latest_counter = backups
+        .iter()
+        .map(|backup| backup_order(&backup.path))
+        .filter(|(backup_stamp, _)| *backup_stamp == stamp.as_str())
+        .map(|(_, counter)| counter)
+        .max();
+    let name = match latest_counter {
+        Some(counter) => format!("mimeapps.list.{}.{}", stamp, counter + 1),
+        None => format!("mimeapps.list.{}", stamp),
+    };
+    write_atomically(&backup_dir.join(name), &content)?;
+
+    for old in list_backups(backup_dir).iter().skip(MAX_BACKUPS) {
+        fs::remove_file(&old.path)?;
+    }
+
+    Ok(())
+}
+
+/// Backups in `backup_dir`, the newest first.
+pub fn list_backups(backup_dir: &Path) -> Vec<Backup> {
+    let Ok(entries) = fs::read_dir(backup_dir) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<Backup> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let stamp = name.strip_prefix("mimeapps.list.")?;
+            Some(Backup {
+                path: entry.path(),
+                created: describe_timestamp(stamp)?,
+            })
+        })
+        .collect();
+
+    backups.sort_by_cached_key(|backup| std::cmp::Reverse(backup_order(&backup.path)));
+    backups
+}
+
+// Orders backups by age: timestamps sort chronologically as strings, and
+// backups of the same second by their counter (`.2` before `.10`)
+fn backup_order(path: &Path) -> (String, u32) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stamp = name.strip_prefix("mimeapps.list.").unwrap_or(&name);
+    match stamp.split_once('.') {
+        Some((stamp, counter)) => (stamp.to_string(), counter.parse().unwrap_or_default()),
+        None => (stamp.to_string(), 0),
+    }
+}
+
+// `YYYYMMDD-HHMMSS[.N]` to `YYYY-MM-DD HH:MM:SS`
+fn describe_timestamp(stamp: &str) -> Option<String> {
+    let stamp = stamp.split('.').next()?;
+    let (date, time) = stamp.split_once('-')?;
+    if date.len() != 8
+        || time.len() != 6
+        || !(date.to_string() + time)
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    Some(format!(
+        "{}-{}-{} {}:{}:{}",
+        &date[0..4],
+        &date[4..6],
+        &date[6..8],
+        &time[0..2],
+        &time[2..4],
+        &time[4..6]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    // An empty directory of its own for each test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pick_def_app-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn writes_go_through_symlinks() {
+        let dir = test_dir("symlink");
+        fs::write(dir.join("dotfiles.list"), "old").unwrap();
+        symlink("dotfiles.list", dir.join("mimeapps.list")).unwrap();
+
+        write_atomically(&dir.join("mimeapps.list"), "new").unwrap();
+
+        let link = fs::symlink_metadata(dir.join("mimeapps.list")).unwrap();
+        assert!(link.file_type().is_symlink());
+        assert_eq!(
+            fs::read_link(dir.join("mimeapps.list")).unwrap(),
+            Path::new("dotfiles.list")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("dotfiles.list")).unwrap(),
+            "new"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_keep_the_file_mode() {
+        let dir = test_dir("mode");
+        let path = dir.join("mimeapps.list");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_atomically(&path, "new").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_rotate() {
+        let dir = test_dir("rotation");
+        let path = dir.join("mimeapps.list");
+        let backup_dir = dir.join("backups");
+        for i in 0..MAX_BACKUPS + 3 {
+            fs::write(&path, format!("version {}", i)).unwrap();
+            create_backup(&path, &backup_dir).unwrap();
+        }
+        // The same contents again need no new backup
+        create_backup(&path, &backup_dir).unwrap();
+
+        let backups = list_backups(&backup_dir);
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert_eq!(
+            fs::read_to_string(&backups[0].path).unwrap(),
+            format!("version {}", MAX_BACKUPS + 2)
+        );
+        assert_eq!(
+            fs::read_to_string(&backups[MAX_BACKUPS - 1].path).unwrap(),
+            "version 3"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_of_the_same_second_sort_by_counter() {
+        let dir = std::env::temp_dir().join(format!("pick_def_app-backups-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "mimeapps.list.20250101-120000.10",
+            "mimeapps.list.20250101-120000",
+            "mimeapps.list.20250101-120000.2",
+            "mimeapps.list.20241231-235959.11",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let names: Vec<String> = list_backups(&dir)
+            .iter()
+            .map(|backup| {
+                backup
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            names,
+            [
+                "mimeapps.list.20250101-120000.10",
+                "mimeapps.list.20250101-120000.2",
+                "mimeapps.list.20250101-120000",
+                "mimeapps.list.20241231-235959.11",
+            ]
+        );
+    }
+}
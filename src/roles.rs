@@ -0,0 +1,112 @@
+// Default applications by role, each covering the types an application of
+// that kind is expected to open
+use crate::desktop_entries::AppEntry;
+use crate::mimetype_manager::WEB_BROWSER_TYPES;
+
+/// A kind of application, like a web browser or a music player.
+#[derive(Debug)]
+pub struct Role {
+    pub name: &'static str,
+    pub icon_name: &'static str,
+    // The desktop file category of applications of this kind, to list them
+    // first
+    pub category: &'static str,
+    // Types set all at once
+    pub mimetypes: &'static [&'static str],
+}
+
+pub const ROLES: &[Role] = &[
+    Role {
+        name: "Web Browser",
+        icon_name: "web-browser-symbolic",
+        category: "WebBrowser",
+        mimetypes: WEB_BROWSER_TYPES,
+    },
+    Role {
+        name: "Email Client",
+        icon_name: "mail-unread-symbolic",
+        category: "Email",
+        mimetypes: &["x-scheme-handler/mailto", "message/rfc822"],
+    },
+    Role {
+        name: "Text Editor",
+        icon_name: "accessories-text-editor-symbolic",
+        category: "TextEditor",
+        mimetypes: &[
+            "text/plain",
+            "text/markdown",
+            "text/x-log",
+            "application/json",
+            "application/xml",
+            "application/x-shellscript",
+            "text/x-python",
+            "text/x-csrc",
+            "text/x-chdr",
+            "text/css",
+        ],
+    },
+    Role {
+        name: "Image Viewer",
+        icon_name: "image-x-generic-symbolic",
+        category: "Viewer",
+        mimetypes: &[
+            "image/png",
+            "image/jpeg",
+            "image/gif",
+            "image/webp",
+            "image/bmp",
+            "image/tiff",
+            "image/svg+xml",
+            "image/avif",
+            "image/heic",
+        ],
+    },
+    Role {
+        name: "Music Player",
+        icon_name: "audio-x-generic-symbolic",
+        category: "Audio",
+        mimetypes: &[
+            "audio/mpeg",
+            "audio/flac",
+            "audio/ogg",
+            "audio/x-vorbis+ogg",
+            "audio/opus",
+            "audio/mp4",
+            "audio/aac",
+            "audio/x-wav",
+            "audio/x-mpegurl",
+        ],
+    },
+    Role {
+        name: "Video Player",
+        icon_name: "video-x-generic-symbolic",
+        category: "Video",
+        mimetypes: &[
+            "video/mp4",
+            "video/x-matroska",
+            "video/webm",
+            "video/quicktime",
+            "video/x-msvideo",
+            "video/mpeg",
+            "video/ogg",
+        ],
+    },
+];
+
+impl Role {
+    /// Whether an application handling `mimetypes` opens at least one of
+    /// the role's types, whatever its categories.
+    pub fn fits(&self, mimetypes: &[String]) -> bool {
+        self.mimetypes
+            .iter()
+            .any(|mimetype| mimetypes.iter().any(|handled| handled == mimetype))
+    }
+
+    /// Whether the application declares the role's category.
+    pub fn is_of_kind(&self, entry: &AppEntry) -> bool {
+        entry
+            .categories
+            .iter()
+            .any(|category| category == self.category)
+    }
+}
@@ -0,0 +1,153 @@
+// Searching the apps list
+use crate::desktop_entries::AppEntry;
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+// How much a match counts, by where it was found
+const NAME_WEIGHT: i64 = 4;
+const GENERIC_NAME_WEIGHT: i64 = 3;
+const KEYWORD_WEIGHT: i64 = 3;
+const CATEGORY_WEIGHT: i64 = 2;
+const EXEC_WEIGHT: i64 = 2;
+const MIMETYPE_WEIGHT: i64 = 1;
+// Comments are only matched word for word, long texts match fuzzily too easily
+const COMMENT_SCORE: i64 = 10;
+
+/// What was typed in the search entry: words matched fuzzily against the
+/// applications, and `mime:` and `cat:` filters (e.g. `mime:image/` or
+/// `cat:Graphics`).
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    // Each word has to match somewhere
+    terms: Vec<String>,
+    // Parts of a type the application handles, lowercased
+    mimetypes: Vec<String>,
+    // Parts of one of its categories, lowercased
+    categories: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(text: &str) -> Self {
+        let mut query = Self::default();
+        for word in text.split_whitespace() {
+            match word.split_once(':') {
+                Some((prefix, value)) if prefix.eq_ignore_ascii_case("mime") => {
+                    if !value.is_empty() {
+                        query.mimetypes.push(value.to_lowercase());
+                    }
+                }
+                Some((prefix, value)) if prefix.eq_ignore_ascii_case("cat") => {
+                    if !value.is_empty() {
+                        query.categories.push(value.to_lowercase());
+                    }
+                }
+                _ => query.terms.push(word.to_string()),
+            }
+        }
+        query
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.mimetypes.is_empty() && self.categories.is_empty()
+    }
+
+    /// How well the application matches, the higher the better, or `None`
+    /// when it doesn't. `mimetypes` are the types it handles, `describe`
+    /// gives their human readable description.
+    pub fn score(
+        &self,
+        entry: &AppEntry,
+        mimetypes: &[String],
+        describe: impl Fn(&str) -> Option<String>,
+    ) -> Option<i64> {
+        let handles_types = self.mimetypes.iter().all(|filter| {
+            mimetypes
+                .iter()
+                .any(|mimetype| mimetype.to_lowercase().contains(filter))
+        });
+        let in_categories = self.categories.iter().all(|filter| {
+            entry
+                .categories
+                .iter()
+                .any(|category| category.to_lowercase().contains(filter))
+        });
+        if !handles_types || !in_categories {
+            return None;
+        }
+
+        let descriptions: Vec<String> = if self.terms.is_empty() {
+            Vec::new()
+        } else {
+            mimetypes
+                .iter()
+                .filter_map(|mimetype| describe(mimetype))
+                .collect()
+        };
+        let exec = exec_binary(&entry.exec);
+
+        let matcher = SkimMatcherV2::default();
+        let mut total = 0;
+        for term in &self.terms {
+            let fields = std::iter::once((entry.name.as_str(), NAME_WEIGHT))
+                .chain(
+                    entry
+                        .generic_name
+                        .iter()
+                        .map(|name| (name.as_str(), GENERIC_NAME_WEIGHT)),
+                )
+                .chain(
+                    entry
+                        .keywords
+                        .iter()
+                        .map(|keyword| (keyword.as_str(), KEYWORD_WEIGHT)),
+                )
+                .chain(
+                    entry
+                        .categories
+                        .iter()
+                        .map(|category| (category.as_str(), CATEGORY_WEIGHT)),
+                )
+                .chain(exec.iter().map(|exec| (*exec, EXEC_WEIGHT)))
+                .chain(
+                    mimetypes
+                        .iter()
+                        .chain(&descriptions)
+                        .map(|text| (text.as_str(), MIMETYPE_WEIGHT)),
+                );
+
+            let fuzzy_score = fields
+                .filter_map(|(text, weight)| Some(matcher.fuzzy_match(text, term)? * weight))
+                .max();
+            let comment_score = entry
+                .comment
+                .as_ref()
+                .filter(|comment| comment.to_lowercase().contains(&term.to_lowercase()))
+                .map(|_| COMMENT_SCORE);
+
+            total += fuzzy_score.max(comment_score)?;
+        }
+        Some(total)
+    }
+
+    /// Positions of the characters of `text` matched by the search words,
+    /// to highlight them.
+    pub fn matched_indices(&self, text: &str) -> BTreeSet<usize> {
+        let matcher = SkimMatcherV2::default();
+        self.terms
+            .iter()
+            .filter_map(|term| matcher.fuzzy_indices(text, term))
+            .flat_map(|(_, indices)| indices)
+            .collect()
+    }
+}
+
+// The program an `Exec` line runs, without its directory, past `env` and
+// its variables
+fn exec_binary(exec: &str) -> Option<&str> {
+    let program = exec
+        .split_whitespace()
+        .find(|word| *word != "env" && !word.contains('='))?
+        .trim_matches('"');
+    Path::new(program).file_name()?.to_str()
+}
@@ -0,0 +1,107 @@
+// Preferences kept between runs, in $XDG_CONFIG_HOME/pick_def_app/settings.ini
+use crate::keyfile::KeyFile;
+use crate::xdg_dirs;
+use std::fs;
+use std::path::PathBuf;
+
+const FILTERS: &str = "Filters";
+const APPLICATIONS: &str = "Applications";
+
+pub struct Settings {
+    path: Option<PathBuf>,
+    document: KeyFile,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            document: KeyFile::new(),
+        }
+    }
+
+    /// Reads the settings file, falling back to the defaults when it's
+    /// missing or unreadable.
+    pub fn load() -> Self {
+        let path = xdg_dirs::config_home().map(|dir| dir.join("pick_def_app").join("settings.ini"));
+        let document = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| KeyFile::parse(&content))
+            .unwrap_or_default();
+
+        Self { path, document }
+    }
+
+    fn get_list(&self, group: &str, key: &str) -> Vec<String> {
+        self.document
+            .get(group, key)
+            .unwrap_or_default()
+            .split(';')
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn set_list(&mut self, group: &str, key: &str, items: &[String]) {
+        let value: String = items.iter().map(|item| format!("{};", item)).collect();
+        self.document.set(group, key, &value);
+    }
+
+    fn get_bool(&self, group: &str, key: &str) -> bool {
+        self.document.get(group, key) == Some("true")
+    }
+
+    fn set_bool(&mut self, group: &str, key: &str, value: bool) {
+        self.document
+            .set(group, key, if value { "true" } else { "false" });
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or("Could not determine the user configuration directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.document.to_string())?;
+        Ok(())
+    }
+
+    /// Major MIME types (e.g. `image`) whose filter chip is turned off.
+    pub fn hidden_types(&self) -> Vec<String> {
+        self.get_list(FILTERS, "HiddenTypes")
+    }
+
+    pub fn set_hidden_types(&mut self, types: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_list(FILTERS, "HiddenTypes", types);
+        self.save()
+    }
+
+    /// Whether hidden and unavailable applications are listed too.
+    pub fn show_unavailable(&self) -> bool {
+        self.get_bool(APPLICATIONS, "ShowUnavailable")
+    }
+
+    pub fn set_show_unavailable(&mut self, show: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_bool(APPLICATIONS, "ShowUnavailable", show);
+        self.save()
+    }
+
+    /// Whether applications declaring no MIME type are listed too.
+    pub fn show_all_apps(&self) -> bool {
+        self.get_bool(APPLICATIONS, "ShowAllApps")
+    }
+
+    pub fn set_show_all_apps(&mut self, show: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_bool(APPLICATIONS, "ShowAllApps", show);
+        self.save()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
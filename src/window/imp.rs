@@ -1,11 +1,30 @@
 // Object holding the state
-use crate::desktop_entries::DesktopEntryManager;
-use crate::mimetype_manager::MimetypeManager;
-use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
-use gtk::prelude::*;
+use crate::app_object::AppObject;
+use crate::desktop_entries::{AppEntry, DesktopEntryManager, MAIN_CATEGORIES};
+use crate::launcher::{self, Launcher};
+use crate::mime_database::{MimeDatabase, is_valid_mimetype};
+use crate::mimetype_manager::{
+    ConflictError, EffectiveDefault, ExternalChangeError, MimetypeManager, PendingChange,
+    WEB_BROWSER_TYPES, scheme, scheme_name,
+};
+use crate::roles::{ROLES, Role};
+use crate::search::SearchQuery;
+use crate::settings::Settings;
+use adw::prelude::*;
 use gtk::subclass::prelude::*;
-use gtk::{CompositeTemplate, Entry, Label, ToggleButton, glib};
-use std::cell::RefCell;
+use gtk::{CompositeTemplate, Entry, Label, ToggleButton, gdk, gio, glib};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+// How long to wait for more changes on disk before reloading
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
+// The model of the "Add application" rows, and the name and desktop file ID
+// of each of its items
+type AppChoices = (gtk::StringList, Rc<Vec<(String, String)>>);
 
 #[derive(CompositeTemplate, Default)]
 #[template(file = "src/window/window.blp")]
@@ -15,6 +34,8 @@ pub struct Window {
     #[template_child]
     pub filter_wrap_box: TemplateChild<adw::WrapBox>,
     #[template_child]
+    pub categories_list_box: TemplateChild<gtk::ListBox>,
+    #[template_child]
     pub apps_list_box: TemplateChild<gtk::ListBox>,
     #[template_child]
     pub app_mime_types_list_box: TemplateChild<gtk::ListBox>,
@@ -24,9 +45,64 @@ pub struct Window {
     pub mime_types_stack: TemplateChild<gtk::Stack>,
     #[template_child]
     pub apply_button: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub view_stack: TemplateChild<adw::ViewStack>,
+    #[template_child]
+    pub mimetypes_scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+    #[template_child]
+    pub mimetypes_view_box: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub roles_scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+    #[template_child]
+    pub roles_view_box: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub links_scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+    #[template_child]
+    pub links_view_box: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub changes_banner: TemplateChild<adw::Banner>,
+    #[template_child]
+    pub toast_overlay: TemplateChild<adw::ToastOverlay>,
     pub desktop_manager: RefCell<DesktopEntryManager>,
     pub mimetype_manager: RefCell<Option<MimetypeManager>>,
-    pub selected_app_name: RefCell<Option<String>>,
+    pub mime_database: RefCell<MimeDatabase>,
+    pub settings: RefCell<Settings>,
+    // Major types whose filter chip is turned off
+    pub hidden_types: RefCell<HashSet<String>>,
+    // Set while chips are toggled programmatically, to filter only once
+    pub updating_filters: Cell<bool>,
+    // Main category picked in the sidebar, all applications when `None`
+    pub selected_category: Cell<Option<&'static str>>,
+    // Categories of the rows in categories_list_box, in order, with their
+    // application count
+    pub displayed_categories: RefCell<Vec<(Option<&'static str>, usize)>>,
+    // Desktop file ID of the selected application
+    pub selected_app_id: RefCell<Option<String>>,
+    pub apps_model: OnceCell<gio::ListStore>,
+    pub apps_filter: OnceCell<gtk::CustomFilter>,
+    pub apps_filter_model: OnceCell<gtk::FilterListModel>,
+    pub search_query: RefCell<SearchQuery>,
+    // Scores of the apps matching the search, by desktop file ID
+    pub search_scores: RefCell<HashMap<String, i64>>,
+    // Set while the rows of the apps list are recreated, so the selection
+    // survives it
+    pub refiltering_apps: Cell<bool>,
+    // List hidden and unavailable applications as well
+    pub show_unavailable: Cell<bool>,
+    // List applications without any mimetype as well
+    pub show_all_apps: Cell<bool>,
+    // Mimetypes of the rows in app_mime_types_list_box, in order
+    pub displayed_mimetypes: RefCell<Vec<String>>,
+    pub expanded_mimetypes: RefCell<HashSet<String>>,
+    pub expanded_roles: RefCell<HashSet<&'static str>>,
+    // Every application by name, built when first needed
+    pub app_choices: RefCell<Option<AppChoices>>,
+    // Watch mimeapps.list files and applications directories
+    pub file_monitors: RefCell<Vec<gio::FileMonitor>>,
+    // What changed on disk since the last reload
+    pub desktop_entries_changed: Cell<bool>,
+    pub mimeapps_changed: Cell<bool>,
+    pub reload_source: RefCell<Option<glib::SourceId>>,
 }
 
 // The central trait for subclassing a GObject
@@ -40,6 +116,70 @@ impl ObjectSubclass for Window {
     fn class_init(klass: &mut Self::Class) {
         klass.bind_template();
         klass.bind_template_callbacks();
+
+        klass.install_action("win.restore-backup", None, |window, _, _| {
+            window.imp().show_backups_dialog();
+        });
+
+        klass.install_action("win.open-file", None, |window, _, _| {
+            window.imp().choose_file();
+        });
+
+        klass.install_action("win.show-diagnostics", None, |window, _, _| {
+            window.imp().show_diagnostics_dialog();
+        });
+
+        klass.install_action("win.add-mimetype", None, |window, _, _| {
+            window.imp().show_add_mimetype_dialog();
+        });
+
+        klass.install_action("win.review-changes", None, |window, _, _| {
+            window.imp().show_review_dialog();
+        });
+
+        klass.install_action("win.save-changes", None, |window, _, _| {
+            window.imp().save_changes();
+        });
+
+        klass.install_action("win.discard-changes", None, |window, _, _| {
+            window.imp().discard_changes();
+        });
+
+        klass.install_action("win.undo", None, |window, _, _| {
+            window.imp().undo_or_redo(false);
+        });
+
+        klass.install_action("win.redo", None, |window, _, _| {
+            window.imp().undo_or_redo(true);
+        });
+
+        klass.install_action("win.show-history", None, |window, _, _| {
+            window.imp().show_history_dialog();
+        });
+
+        klass.install_action(
+            "win.show-only-type",
+            Some(glib::VariantTy::STRING),
+            |window, _, parameter| {
+                if let Some(main_type) = parameter.and_then(|parameter| parameter.get::<String>()) {
+                    window
+                        .imp()
+                        .set_filter_chips(|chip_type| chip_type == main_type);
+                }
+            },
+        );
+
+        klass.install_action("win.choose-browser", None, |window, _, _| {
+            window.imp().show_browser_dialog();
+        });
+
+        klass.install_action("win.new-launcher", None, |window, _, _| {
+            window.imp().show_launcher_dialog(Launcher::new());
+        });
+
+        klass.install_action("win.edit-launcher", None, |window, _, _| {
+            window.imp().edit_selected_launcher();
+        });
     }
 
     fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -60,6 +200,32 @@ impl ObjectImpl for Window {
         }
         self.desktop_manager.replace(desktop_manager);
 
+        self.mime_database.replace(MimeDatabase::load());
+
+        let settings = Settings::load();
+        self.hidden_types
+            .replace(settings.hidden_types().into_iter().collect());
+        self.show_unavailable.set(settings.show_unavailable());
+        self.show_all_apps.set(settings.show_all_apps());
+        self.settings.replace(settings);
+
+        self.obj().add_action_entries([
+            toggle_action(
+                "show-unavailable",
+                self.show_unavailable.get(),
+                |imp, show| {
+                    imp.show_unavailable.set(show);
+                    imp.save_settings(|settings| settings.set_show_unavailable(show));
+                },
+            ),
+            toggle_action("show-all-apps", self.show_all_apps.get(), |imp, show| {
+                imp.show_all_apps.set(show);
+                imp.save_settings(|settings| settings.set_show_all_apps(show));
+            }),
+        ]);
+        self.obj().action_set_enabled("win.add-mimetype", false);
+        self.obj().action_set_enabled("win.edit-launcher", false);
+
         match MimetypeManager::new() {
             Ok(mimetype_manager) => {
                 self.mimetype_manager.replace(Some(mimetype_manager));
@@ -69,9 +235,15 @@ impl ObjectImpl for Window {
             }
         }
 
+        // Save, discard, undo and redo follow the staged changes
+        self.update_changes_banner();
+
         // Populate filter buttons with categories
         self.populate_filter_buttons();
 
+        // Picking a category in the sidebar filters the apps list
+        self.setup_categories();
+
         // Populate apps list with all applications
         self.populate_apps_list();
 
@@ -80,6 +252,15 @@ impl ObjectImpl for Window {
 
         // Set up app selection handler
         self.setup_app_selection();
+
+        // Dropping a file shows what opens it
+        self.setup_file_drop();
+
+        // Build the MIME types view whenever it is shown, so it's up to date
+        self.setup_mimetypes_view();
+
+        // Follow changes made by other programs and package managers
+        self.setup_file_monitors();
     }
 }
 
@@ -87,7 +268,50 @@ impl ObjectImpl for Window {
 impl WidgetImpl for Window {}
 
 // Trait shared by all windows
-impl WindowImpl for Window {}
+impl WindowImpl for Window {
+    // Ask before unsaved changes are lost
+    fn close_request(&self) -> glib::Propagation {
+        if !self.has_pending_changes() {
+            return self.parent_close_request();
+        }
+
+        let dialog = adw::AlertDialog::new(
+            Some("Save Changes?"),
+            Some(
+                "The default applications were changed but not saved. Unsaved changes are lost when closing.",
+            ),
+        );
+        dialog.add_responses(&[
+            ("cancel", "Cancel"),
+            ("discard", "Discard"),
+            ("save", "Save"),
+        ]);
+        dialog.set_response_appearance("discard", adw::ResponseAppearance::Destructive);
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+
+        let obj_weak = self.obj().downgrade();
+        dialog.connect_response(None, move |_, response| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+            match response {
+                "save" => imp.save_changes(),
+                "discard" => imp.discard_changes(),
+                _ => return,
+            }
+            // Saving can fail, or wait for a conflict to be resolved
+            if !imp.has_pending_changes() {
+                obj.close();
+            }
+        });
+        dialog.present(Some(&*self.obj()));
+
+        glib::Propagation::Stop
+    }
+}
 
 // Trait shared by all application windows
 impl ApplicationWindowImpl for Window {}
@@ -106,260 +330,2992 @@ impl Window {
         }
 
         // Add toggle buttons for each main mimetype category
+        let hidden_types = self.hidden_types.borrow();
+        let only_menu = gio::Menu::new();
         for main_type in main_types {
-            let button = ToggleButton::builder().label(&main_type).build();
-            button.set_active(true);
+            let item = gio::MenuItem::new(Some(&main_type), None);
+            item.set_action_and_target_value(
+                Some("win.show-only-type"),
+                Some(&main_type.to_variant()),
+            );
+            only_menu.append_item(&item);
+
+            let button = ToggleButton::builder()
+                .label(&main_type)
+                .tooltip_text("Right-click to show only this type")
+                .build();
+            button.set_active(!hidden_types.contains(&main_type));
 
             // It's so huge, it doesn't convince me
             // button.add_css_class("round");
 
+            let obj_weak = self.obj().downgrade();
+            button.connect_toggled(move |_| {
+                if let Some(obj) = obj_weak.upgrade() {
+                    obj.imp().on_filter_chips_changed();
+                }
+            });
+
+            // Right-click shows this type only
+            let gesture = gtk::GestureClick::builder()
+                .button(gdk::BUTTON_SECONDARY)
+                .build();
+            let obj_weak = self.obj().downgrade();
+            gesture.connect_pressed(move |_, _, _, _| {
+                if let Some(obj) = obj_weak.upgrade() {
+                    obj.imp()
+                        .set_filter_chips(|chip_type| chip_type == main_type);
+                }
+            });
+            button.add_controller(gesture);
+
             self.filter_wrap_box.append(&button);
         }
+
+        for (label, active) in [("All", true), ("None", false)] {
+            let button = gtk::Button::builder().label(label).build();
+            button.add_css_class("flat");
+
+            let obj_weak = self.obj().downgrade();
+            button.connect_clicked(move |_| {
+                if let Some(obj) = obj_weak.upgrade() {
+                    obj.imp().set_filter_chips(|_| active);
+                }
+            });
+
+            self.filter_wrap_box.append(&button);
+        }
+
+        let only_button = gtk::MenuButton::builder()
+            .label("Only…")
+            .tooltip_text("Show a single type")
+            .menu_model(&only_menu)
+            .build();
+        only_button.add_css_class("flat");
+        self.filter_wrap_box.append(&only_button);
+    }
+
+    fn filter_chips(&self) -> Vec<ToggleButton> {
+        let mut chips = Vec::new();
+        let mut child = self.filter_wrap_box.first_child();
+        while let Some(widget) = child {
+            child = widget.next_sibling();
+            if let Ok(chip) = widget.downcast::<ToggleButton>() {
+                chips.push(chip);
+            }
+        }
+        chips
+    }
+
+    fn set_filter_chips(&self, is_active: impl Fn(&str) -> bool) {
+        self.updating_filters.set(true);
+        for chip in self.filter_chips() {
+            chip.set_active(is_active(&chip.label().unwrap_or_default()));
+        }
+        self.updating_filters.set(false);
+
+        self.on_filter_chips_changed();
+    }
+
+    fn on_filter_chips_changed(&self) {
+        if self.updating_filters.get() {
+            return;
+        }
+
+        let hidden_types: Vec<String> = self
+            .filter_chips()
+            .iter()
+            .filter(|chip| !chip.is_active())
+            .filter_map(|chip| chip.label())
+            .map(|label| label.to_string())
+            .collect();
+        self.save_settings(|settings| settings.set_hidden_types(&hidden_types));
+        self.hidden_types
+            .replace(hidden_types.into_iter().collect());
+
+        self.refilter_apps();
+        self.app_mime_types_list_box.invalidate_filter();
+        if self.view_stack.visible_child_name().as_deref() == Some("mimetypes") {
+            self.populate_mimetypes_view();
+        }
+    }
+
+    fn save_settings<F>(&self, update: F)
+    where
+        F: FnOnce(&mut Settings) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        if let Err(e) = update(&mut self.settings.borrow_mut()) {
+            eprintln!("Failed to save settings: {}", e);
+        }
+    }
+
+    // Whether the filter chip of the major type of `mimetype` is turned on
+    fn is_type_shown(&self, mimetype: &str) -> bool {
+        let major_type = mimetype.split('/').next().unwrap_or_default();
+        !self.hidden_types.borrow().contains(major_type)
+    }
+
+    // Apps are shown when they're in the selected category and handle at
+    // least one shown type
+    fn is_app_shown(&self, entry: &AppEntry) -> bool {
+        let in_category = self
+            .selected_category
+            .get()
+            .is_none_or(|category| entry.main_categories().contains(&category));
+
+        in_category
+            && (entry.mimetypes.is_empty()
+                || entry
+                    .mimetypes
+                    .iter()
+                    .any(|mimetype| self.is_type_shown(mimetype)))
+    }
+
+    fn setup_categories(&self) {
+        let obj_weak = self.obj().downgrade();
+        self.categories_list_box
+            .connect_row_selected(move |_, row| {
+                let Some(obj) = obj_weak.upgrade() else {
+                    return;
+                };
+                let imp = obj.imp();
+
+                // Rows are being recreated
+                let Some(category) = row.and_then(|row| {
+                    let index = usize::try_from(row.index()).ok()?;
+                    imp.displayed_categories
+                        .borrow()
+                        .get(index)
+                        .map(|(category, _)| *category)
+                }) else {
+                    return;
+                };
+
+                if imp.selected_category.replace(category) != category {
+                    imp.refilter_apps();
+                }
+            });
+    }
+
+    // Lists the main categories of `entries`, the listed apps, with how many
+    // apps are in each
+    fn populate_categories(&self, entries: &[&AppEntry]) {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for entry in entries {
+            for category in entry.main_categories() {
+                *counts.entry(category).or_default() += 1;
+            }
+        }
+
+        let categories: Vec<(Option<&'static str>, usize)> = std::iter::once((None, entries.len()))
+            .chain(MAIN_CATEGORIES.iter().filter_map(|category| {
+                let count = counts.get(category.id).copied()?;
+                Some((Some(category.id), count))
+            }))
+            .collect();
+
+        // Searching repopulates the apps list, without changing the counts
+        if *self.displayed_categories.borrow() == categories {
+            return;
+        }
+        self.displayed_categories.replace(categories.clone());
+
+        while let Some(child) = self.categories_list_box.first_child() {
+            self.categories_list_box.remove(&child);
+        }
+
+        for (category, count) in &categories {
+            let label = match category {
+                Some(id) => MAIN_CATEGORIES
+                    .iter()
+                    .find(|category| category.id == *id)
+                    .map_or(*id, |category| category.label),
+                None => "All Applications",
+            };
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            let name = Label::new(Some(label));
+            name.set_halign(gtk::Align::Start);
+            name.set_hexpand(true);
+            name.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            row.append(&name);
+
+            let count = badge(&count.to_string());
+            count.add_css_class("numeric");
+            row.append(&count);
+
+            self.categories_list_box.append(&row);
+        }
+
+        // Keep the category picked, unless it has no apps left
+        let selected = self
+            .selected_category
+            .get()
+            .and_then(|selected| {
+                categories
+                    .iter()
+                    .position(|(category, _)| *category == Some(selected))
+            })
+            .unwrap_or(0);
+        if selected == 0 {
+            self.selected_category.set(None);
+        }
+        let row = i32::try_from(selected)
+            .ok()
+            .and_then(|index| self.categories_list_box.row_at_index(index));
+        self.categories_list_box.select_row(row.as_ref());
+    }
+
+    fn apps_model(&self) -> &gio::ListStore {
+        self.apps_model
+            .get_or_init(gio::ListStore::new::<AppObject>)
     }
 
     pub fn populate_apps_list(&self) {
         let desktop_manager = self.desktop_manager.borrow();
-        let entries = desktop_manager.get_entries();
+        let show_unavailable = self.show_unavailable.get();
+        let show_all_apps = self.show_all_apps.get();
+        let mut entries: Vec<&AppEntry> = desktop_manager
+            .get_all_entries()
+            .into_iter()
+            .filter(|entry| show_unavailable || entry.is_listed())
+            .filter(|entry| show_all_apps || !self.app_mimetypes(entry).is_empty())
+            .collect();
+        entries.sort_by_cached_key(|entry| (entry.name.to_lowercase(), entry.id.clone()));
 
-        // Clear existing children
-        while let Some(child) = self.apps_list_box.first_child() {
-            self.apps_list_box.remove(&child);
+        // Rank the apps against the search, the list is sorted by score
+        let query = self.search_query.borrow();
+        let scores = if query.is_empty() {
+            HashMap::new()
+        } else {
+            let mime_database = self.mime_database.borrow();
+            entries
+                .iter()
+                .filter_map(|entry| {
+                    let score = query.score(entry, &self.app_mimetypes(entry), |mimetype| {
+                        mime_database.description(mimetype)
+                    })?;
+                    Some((entry.id.clone(), score))
+                })
+                .collect()
+        };
+        drop(query);
+        self.search_scores.replace(scores);
+
+        self.populate_categories(&entries);
+
+        let apps: Vec<AppObject> = entries
+            .into_iter()
+            .map(|entry| AppObject::new(entry.clone()))
+            .collect();
+        let apps_model = self.apps_model();
+        self.keeping_app_selection(|| apps_model.splice(0, apps_model.n_items(), &apps));
+    }
+
+    // The mimetypes an app declares, then those the user associated it with
+    fn app_mimetypes(&self, entry: &AppEntry) -> Vec<String> {
+        let mut mimetypes = entry.mimetypes.clone();
+        if let Some(mimetype_manager) = self.mimetype_manager.borrow().as_ref() {
+            for mimetype in mimetype_manager.get_mimetypes_for_app(&entry.id) {
+                if !mimetypes.contains(&mimetype) {
+                    mimetypes.push(mimetype);
+                }
+            }
         }
+        mimetypes
+    }
 
-        // Add a row for each application
-        for entry in entries {
-            let label = Label::new(Some(&entry.name));
-            label.set_halign(gtk::Align::Start);
-            label.set_margin_start(12);
-            label.set_margin_end(12);
-            label.set_margin_top(8);
-            label.set_margin_bottom(8);
+    // Applies the search text and filter chips to the apps list again
+    fn refilter_apps(&self) {
+        if let Some(apps_filter) = self.apps_filter.get() {
+            self.keeping_app_selection(|| apps_filter.changed(gtk::FilterChange::Different));
+        }
+    }
 
-            // Use the label text itself to identify the app
-            self.apps_list_box.append(&label);
+    // Runs `update`, which recreates rows of the apps list, then selects the
+    // app that was selected again
+    fn keeping_app_selection(&self, update: impl FnOnce()) {
+        self.refiltering_apps.set(true);
+        update();
+        self.refiltering_apps.set(false);
+
+        // Rows are recreated, select the app again if it's still listed
+        let selected_app_id = self.selected_app_id.borrow().clone();
+        if let Some(app_id) = selected_app_id {
+            match self.app_row(&app_id) {
+                Some(row) => self.apps_list_box.select_row(Some(&row)),
+                None => self.apps_list_box.unselect_all(),
+            }
         }
     }
 
-    pub fn populate_app_mimetypes(&self, app_name: &str) {
+    // The row of the app with this desktop file ID, if it passes the filter
+    fn app_row(&self, app_id: &str) -> Option<gtk::ListBoxRow> {
+        let apps_filter_model = self.apps_filter_model.get()?;
+        let position = (0..apps_filter_model.n_items()).find(|&position| {
+            apps_filter_model
+                .item(position)
+                .and_downcast::<AppObject>()
+                .is_some_and(|app| app.entry().id == app_id)
+        })?;
+        self.apps_list_box
+            .row_at_index(i32::try_from(position).ok()?)
+    }
+
+    pub fn populate_app_mimetypes(&self, app_id: &str) {
         let desktop_manager = self.desktop_manager.borrow();
-        let entries = desktop_manager.get_entries();
 
         // Clear existing children
         while let Some(child) = self.app_mime_types_list_box.first_child() {
             self.app_mime_types_list_box.remove(&child);
         }
+        self.displayed_mimetypes.borrow_mut().clear();
 
         // Find the selected app and show its mimetypes
-        if let Some(app_entry) = entries.iter().find(|entry| entry.name == app_name) {
-            if app_entry.mimetypes.is_empty() {
+        if let Some(app_entry) = desktop_manager.get_any_entry(app_id) {
+            let app_mimetypes = self.app_mimetypes(app_entry);
+            if app_mimetypes.is_empty() {
                 // App selected but no mimetypes - show no_mime_types_page
                 self.mime_types_stack
                     .set_visible_child_name("no_mime_types_page");
             } else {
                 // App has mimetypes - populate list and show list page
-                for mimetype in &app_entry.mimetypes {
-                    let label = Label::new(Some(mimetype));
-                    label.set_halign(gtk::Align::Start);
-                    label.set_margin_start(12);
-                    label.set_margin_end(12);
-                    label.set_margin_top(8);
-                    label.set_margin_bottom(8);
+                let mime_database = self.mime_database.borrow();
+                for mimetype in &app_mimetypes {
+                    let row = adw::ActionRow::builder()
+                        .title(
+                            mime_database
+                                .description(mimetype)
+                                .unwrap_or_else(|| mimetype.clone()),
+                        )
+                        .subtitle(describe_mimetype(&mime_database, mimetype))
+                        .use_markup(false)
+                        .build();
+                    row.add_prefix(&gtk::Image::from_icon_name(
+                        &mime_database.icon_name(mimetype),
+                    ));
 
-                    self.app_mime_types_list_box.append(&label);
+                    self.app_mime_types_list_box.append(&row);
+                    self.displayed_mimetypes.borrow_mut().push(mimetype.clone());
                 }
                 self.mime_types_stack
                     .set_visible_child_name("app_mime_types_list_box_page");
 
                 // Populate the active apps for these mimetypes
-                self.populate_active_mimetypes(&app_entry.mimetypes);
+                self.populate_active_mimetypes(&app_mimetypes);
             }
         }
     }
 
     pub fn populate_active_mimetypes(&self, mimetypes: &[String]) {
-        // Clear existing children
         while let Some(child) = self.active_app_mime_types_list_box.first_child() {
             self.active_app_mime_types_list_box.remove(&child);
         }
 
+        let selected_app = self.selected_desktop_file();
+
+        // Every known application, as candidates for added associations
+        let (app_names, all_apps) = self.app_choices();
+        let display_name = |desktop_file: &str| {
+            all_apps
+                .iter()
+                .find(|(_, file)| file == desktop_file)
+                .map_or(desktop_file.to_string(), |(name, _)| name.clone())
+        };
+
+        let desktop_manager = self.desktop_manager.borrow();
+        let mime_database = self.mime_database.borrow();
         if let Some(mimetype_manager) = self.mimetype_manager.borrow().as_ref() {
             for mimetype in mimetypes {
-                let default_app = mimetype_manager.get_default_app(mimetype);
-
-                // Create a box to hold mimetype and default app
-                let row_box = gtk::Box::new(gtk::Orientation::Vertical, 4);
-                row_box.set_margin_start(12);
-                row_box.set_margin_end(12);
-                row_box.set_margin_top(8);
-                row_box.set_margin_bottom(8);
-
-                // Mimetype label
-                let mimetype_label = Label::new(Some(mimetype));
-                mimetype_label.set_halign(gtk::Align::Start);
-                mimetype_label.add_css_class("caption");
-                mimetype_label.add_css_class("dim-label");
-
-                // Default app label
-                let default_label = if let Some(app) = default_app {
-                    Label::new(Some(&format!("→ {}", app)))
-                } else {
-                    let label = Label::new(Some("→ No default set"));
-                    label.add_css_class("dim-label");
-                    label
+                let is_installed = |app: &str| desktop_manager.has_desktop_file(app);
+                let explicit_default =
+                    mimetype_manager.get_effective_default(mimetype, is_installed);
+                let inherited_default =
+                    mimetype_manager.get_inherited_default(mimetype, &mime_database, is_installed);
+
+                // Point out defaults that don't come from the file we edit
+                let from = |default: &EffectiveDefault| {
+                    if default.source.as_path() == mimetype_manager.user_config_path() {
+                        String::new()
+                    } else {
+                        format!(" (from {})", default.source.display())
+                    }
                 };
-                default_label.set_halign(gtk::Align::Start);
 
-                row_box.append(&mimetype_label);
-                row_box.append(&default_label);
+                // Without a default of its own, a type opens with the one of
+                // an alias or a parent type
+                let subtitle = match (&explicit_default, &inherited_default) {
+                    (Some(default), _) => {
+                        format!("{} → {}{}", mimetype, default.desktop_file, from(default))
+                    }
+                    (None, Some(inherited)) => format!(
+                        "{} → No default set, opens with {} inherited from {}{}",
+                        mimetype,
+                        inherited.desktop_file,
+                        inherited.mimetype,
+                        from(inherited)
+                    ),
+                    (None, None) => format!("{} → No default set", mimetype),
+                };
 
-                self.active_app_mime_types_list_box.append(&row_box);
-            }
-        }
-    }
+                // Keep rows expanded across refreshes, so an edit doesn't
+                // collapse what the user is working on
+                let row = adw::ExpanderRow::builder()
+                    .title(
+                        mime_database
+                            .description(mimetype)
+                            .unwrap_or_else(|| mimetype.clone()),
+                    )
+                    .subtitle(subtitle)
+                    .use_markup(false)
+                    .expanded(self.expanded_mimetypes.borrow().contains(mimetype))
+                    .build();
 
-    pub fn setup_filtering(&self) {
-        let filter_entry = self.filter_entry.clone();
+                row.add_prefix(&gtk::Image::from_icon_name(
+                    &mime_database.icon_name(mimetype),
+                ));
 
-        // Set up filter function for the list box
-        self.apps_list_box.set_filter_func(move |row| {
-            let filter_text = filter_entry.text();
+                let obj_weak = self.obj().downgrade();
+                let expanded_mimetype = mimetype.clone();
+                row.connect_expanded_notify(move |row| {
+                    if let Some(obj) = obj_weak.upgrade() {
+                        let mut expanded = obj.imp().expanded_mimetypes.borrow_mut();
+                        if row.is_expanded() {
+                            expanded.insert(expanded_mimetype.clone());
+                        } else {
+                            expanded.remove(&expanded_mimetype);
+                        }
+                    }
+                });
 
-            // If no filter text, show all items
-            if filter_text.is_empty() {
-                return true;
-            }
+                // Let the selected application be hidden from this type
+                if let Some(app) = &selected_app {
+                    let removed = mimetype_manager
+                        .get_removed_associations(mimetype)
+                        .contains(app);
+                    let switch_row = adw::SwitchRow::builder()
+                        .title("Offer selected application")
+                        .subtitle("Turn off to hide it from “Open With” for this type")
+                        .active(!removed)
+                        .build();
 
-            // Get the label from the row
-            if let Some(child) = row.child()
-                && let Ok(label) = child.downcast::<Label>()
-            {
-                let app_name = label.text();
-                let matcher = SkimMatcherV2::default();
-                let matches = matcher.fuzzy_match(&app_name, &filter_text).is_some();
-                return matches;
-            }
+                    let obj_weak = self.obj().downgrade();
+                    let (mimetype, app, mimetypes) =
+                        (mimetype.clone(), app.clone(), mimetypes.to_vec());
+                    let app_name = display_name(&app);
+                    switch_row.connect_active_notify(move |switch_row| {
+                        if let Some(obj) = obj_weak.upgrade() {
+                            let active = switch_row.is_active();
+                            let description = if active {
+                                format!("Offer {} for {}", app_name, mimetype)
+                            } else {
+                                format!("Hide {} from {}", app_name, mimetype)
+                            };
+                            obj.imp()
+                                .update_associations(&mimetypes, &description, |manager| {
+                                    if active {
+                                        manager.reset_association(&mimetype, &app)
+                                    } else {
+                                        manager.remove_association(&mimetype, &app)
+                                    }
+                                });
+                        }
+                    });
+                    row.add_row(&switch_row);
+                }
 
-            false
-        });
-    }
+                // Ordered defaults, reorderable by dragging
+                for (position, app) in mimetype_manager
+                    .get_default_apps(mimetype)
+                    .iter()
+                    .enumerate()
+                {
+                    let default_row = adw::ActionRow::builder()
+                        .title(display_name(app))
+                        .subtitle(if position == 0 {
+                            "Preferred default".to_string()
+                        } else {
+                            format!("Fallback {}", position)
+                        })
+                        .use_markup(false)
+                        .build();
+                    default_row
+                        .add_prefix(&gtk::Image::from_icon_name("list-drag-handle-symbolic"));
 
-    pub fn setup_app_selection(&self) {
-        let obj = self.obj();
-        let obj_weak = obj.downgrade();
+                    let (m, a, ms) = (mimetype.clone(), app.clone(), mimetypes.to_vec());
+                    let description = format!(
+                        "Remove {} from the defaults of {}",
+                        display_name(app),
+                        mimetype
+                    );
+                    default_row.add_suffix(&self.association_button(
+                        "list-remove-symbolic",
+                        "Remove default",
+                        move |imp| {
+                            imp.update_associations(&ms, &description, |manager| {
+                                manager.remove_default_app(&m, &a)
+                            });
+                        },
+                    ));
 
-        self.apps_list_box.connect_row_selected(move |_, row| {
-            if let Some(obj) = obj_weak.upgrade() {
-                let imp = obj.imp();
-                if let Some(row) = row {
-                    if let Some(child) = row.child()
-                        && let Ok(label) = child.downcast::<Label>()
-                    {
-                        let app_name = label.text();
-                        imp.selected_app_name.replace(Some(app_name.to_string()));
-                        imp.populate_app_mimetypes(&app_name);
-                    }
-                } else {
-                    // No row selected - clear mimetypes and show no_app_selected_page
-                    imp.selected_app_name.replace(None);
-                    while let Some(child) = imp.app_mime_types_list_box.first_child() {
-                        imp.app_mime_types_list_box.remove(&child);
-                    }
-                    while let Some(child) = imp.active_app_mime_types_list_box.first_child() {
-                        imp.active_app_mime_types_list_box.remove(&child);
-                    }
-                    imp.mime_types_stack
-                        .set_visible_child_name("no_app_selected_page");
+                    self.setup_default_reordering(&default_row, mimetype, app, position, mimetypes);
+                    row.add_row(&default_row);
                 }
+
+                let associations = mimetype_manager
+                    .get_added_associations(mimetype)
+                    .iter()
+                    .map(|app| (app, "Added association"))
+                    .chain(
+                        mimetype_manager
+                            .get_removed_associations(mimetype)
+                            .iter()
+                            .map(|app| (app, "Removed association")),
+                    );
+                for (app, kind) in associations {
+                    let association_row = adw::ActionRow::builder()
+                        .title(display_name(app))
+                        .subtitle(kind)
+                        .use_markup(false)
+                        .build();
+
+                    let (m, a, ms) = (mimetype.clone(), app.clone(), mimetypes.to_vec());
+                    let description = format!(
+                        "Reset the association of {} with {}",
+                        display_name(app),
+                        mimetype
+                    );
+                    association_row.add_suffix(&self.association_button(
+                        "edit-undo-symbolic",
+                        "Reset association",
+                        move |imp| {
+                            imp.update_associations(&ms, &description, |manager| {
+                                manager.reset_association(&m, &a)
+                            });
+                        },
+                    ));
+                    row.add_row(&association_row);
+                }
+
+                // Add any application as a default or to the "Open With" list
+                let add_row = adw::ComboRow::builder()
+                    .title("Add application")
+                    .model(&app_names)
+                    .expression(gtk::PropertyExpression::new(
+                        gtk::StringObject::static_type(),
+                        None::<&gtk::Expression>,
+                        "string",
+                    ))
+                    .enable_search(true)
+                    .build();
+
+                for (icon_name, tooltip, as_default) in [
+                    ("emblem-default-symbolic", "Add to defaults", true),
+                    ("list-add-symbolic", "Add association", false),
+                ] {
+                    let add_row_weak = add_row.downgrade();
+                    let apps = Rc::clone(&all_apps);
+                    let (mimetype, mimetypes) = (mimetype.clone(), mimetypes.to_vec());
+                    add_row.add_suffix(&self.association_button(icon_name, tooltip, move |imp| {
+                        let Some((name, app)) = add_row_weak
+                            .upgrade()
+                            .and_then(|add_row| apps.get(add_row.selected() as usize))
+                        else {
+                            return;
+                        };
+
+                        let description = if as_default {
+                            format!("Add {} to the defaults of {}", name, mimetype)
+                        } else {
+                            format!("Associate {} with {}", name, mimetype)
+                        };
+                        imp.update_associations(&mimetypes, &description, |manager| {
+                            if as_default {
+                                manager.add_fallback_app(&mimetype, app)
+                            } else {
+                                manager.add_association(&mimetype, app)
+                            }
+                        });
+                    }));
+                }
+                row.add_row(&add_row);
+
+                self.active_app_mime_types_list_box.append(&row);
             }
-        });
+        }
     }
-}
 
-#[gtk::template_callbacks]
-impl Window {
-    #[template_callback]
-    fn update_apps_list(&self) {
-        self.apps_list_box.invalidate_filter();
+    // Shared by every type row, as rebuilding a list of all applications
+    // for each of them is slow
+    fn app_choices(&self) -> AppChoices {
+        self.app_choices
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                let mut apps: Vec<(String, String)> = self
+                    .desktop_manager
+                    .borrow()
+                    .get_entries()
+                    .iter()
+                    .map(|entry| (entry.name.clone(), entry.id.clone()))
+                    .collect();
+                apps.sort();
+                let names: Vec<&str> = apps.iter().map(|(name, _)| name.as_str()).collect();
+                (gtk::StringList::new(&names), Rc::new(apps))
+            })
+            .clone()
     }
 
-    #[template_callback]
-    fn on_apply_clicked(&self) {
-        // Get the selected app name
-        let selected_app_name = match self.selected_app_name.borrow().as_ref() {
-            Some(name) => name.clone(),
-            None => {
-                eprintln!("No app selected");
-                return;
+    fn setup_mimetypes_view(&self) {
+        let obj_weak = self.obj().downgrade();
+        self.view_stack
+            .connect_visible_child_name_notify(move |view_stack| {
+                let Some(obj) = obj_weak.upgrade() else {
+                    return;
+                };
+                match view_stack.visible_child_name().as_deref() {
+                    Some("roles") => obj.imp().populate_roles_view(),
+                    Some("mimetypes") => obj.imp().populate_mimetypes_view(),
+                    Some("links") => obj.imp().populate_links_view(),
+                    _ => {}
+                }
+            });
+    }
+
+    /// Lists every MIME type that an application declares or that has a
+    /// default, grouped by major type.
+    pub fn populate_mimetypes_view(&self) {
+        while let Some(child) = self.mimetypes_view_box.first_child() {
+            self.mimetypes_view_box.remove(&child);
+        }
+
+        let mut mimetypes: BTreeSet<String> = self
+            .desktop_manager
+            .borrow()
+            .get_all_mimetypes()
+            .into_iter()
+            .collect();
+        if let Some(mimetype_manager) = self.mimetype_manager.borrow().as_ref() {
+            mimetypes.extend(mimetype_manager.get_all_mimetypes());
+        }
+
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for mimetype in mimetypes {
+            let major_type = mimetype.split('/').next().unwrap_or_default().to_string();
+            groups.entry(major_type).or_default().push(mimetype);
+        }
+
+        for (major_type, mimetypes) in groups {
+            if self.hidden_types.borrow().contains(&major_type) {
+                continue;
             }
-        };
 
-        // Get the desktop file name and mimetypes - clone them to avoid borrow issues
-        let (desktop_file_name, app_mimetypes) = {
-            let desktop_manager = self.desktop_manager.borrow();
-            let entries = desktop_manager.get_entries();
+            let group = adw::PreferencesGroup::builder().title(&major_type).build();
+            for mimetype in &mimetypes {
+                group.add(&self.mimetype_view_row(mimetype));
+            }
+            self.mimetypes_view_box.append(&group);
+        }
+    }
 
-            let app_entry = match entries.iter().find(|entry| entry.name == selected_app_name) {
-                Some(entry) => entry,
-                None => {
-                    eprintln!("Could not find app entry for {}", selected_app_name);
-                    return;
-                }
-            };
+    /// Lists each role, with the applications opening its types and those
+    /// that could take it over.
+    pub fn populate_roles_view(&self) {
+        while let Some(child) = self.roles_view_box.first_child() {
+            self.roles_view_box.remove(&child);
+        }
 
-            // Get the desktop file name (e.g., "firefox.desktop")
-            let desktop_file_name = match app_entry.path.file_name() {
-                Some(name) => name.to_string_lossy().to_string(),
-                None => {
-                    eprintln!("Could not get desktop file name from path");
-                    return;
-                }
-            };
+        let group = adw::PreferencesGroup::builder()
+            .title("Default Apps")
+            .description("Set the application for a whole kind of files and links at once")
+            .build();
+        for role in ROLES {
+            group.add(&self.role_row(role));
+        }
+        self.roles_view_box.append(&group);
+    }
 
-            (desktop_file_name, app_entry.mimetypes.clone())
-        }; // desktop_manager borrow ends here
+    fn role_row(&self, role: &'static Role) -> adw::ExpanderRow {
+        let desktop_manager = self.desktop_manager.borrow();
+        let is_installed = |app: &str| desktop_manager.has_desktop_file(app);
 
-        // Get all selected mimetypes from the list box
-        let mut selected_mimetypes = Vec::new();
-        let mut row_index = 0;
-        while let Some(row) = self.app_mime_types_list_box.row_at_index(row_index) {
-            if row.is_selected() {
-                if let Some(child) = row.child()
-                    && let Ok(label) = child.downcast::<Label>()
+        // Applications opening the role's types, with the types each opens
+        let mut handlers: Vec<(String, Vec<&'static str>)> = Vec::new();
+        if let Some(mimetype_manager) = self.mimetype_manager.borrow().as_ref() {
+            for &mimetype in role.mimetypes {
+                let Some(default) = mimetype_manager.get_effective_default(mimetype, is_installed)
+                else {
+                    continue;
+                };
+                match handlers
+                    .iter_mut()
+                    .find(|(app, _)| *app == default.desktop_file)
                 {
-                    selected_mimetypes.push(label.text().to_string());
+                    Some((_, mimetypes)) => mimetypes.push(mimetype),
+                    None => handlers.push((default.desktop_file, vec![mimetype])),
                 }
             }
-            row_index += 1;
         }
 
-        if selected_mimetypes.is_empty() {
-            eprintln!("No mimetypes selected");
-            return;
+        let subtitle = match handlers.as_slice() {
+            [] => "Not set".to_string(),
+            [(app, mimetypes)] if mimetypes.len() == role.mimetypes.len() => self.app_name(app),
+            [(app, mimetypes)] => format!(
+                "{} ({} of {} types, the others aren't set)",
+                self.app_name(app),
+                mimetypes.len(),
+                role.mimetypes.len()
+            ),
+            _ => handlers
+                .iter()
+                .map(|(app, mimetypes)| format!("{} ({})", self.app_name(app), mimetypes.len()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+
+        let row = adw::ExpanderRow::builder()
+            .title(role.name)
+            .subtitle(subtitle)
+            .use_markup(false)
+            .build();
+        row.add_prefix(&gtk::Image::from_icon_name(role.icon_name));
+
+        // Which application opens which type, when they're split
+        if handlers.len() > 1 {
+            let icon = gtk::Image::from_icon_name("dialog-warning-symbolic");
+            icon.add_css_class("warning");
+            icon.set_tooltip_text(Some("Opened by different applications"));
+            row.add_suffix(&icon);
+
+            for (app, mimetypes) in &handlers {
+                let handler_row = adw::ActionRow::builder()
+                    .title(format!("Opened with {}", self.app_name(app)))
+                    .subtitle(mimetypes.join(", "))
+                    .subtitle_lines(0)
+                    .use_markup(false)
+                    .build();
+                row.add_row(&handler_row);
+            }
         }
 
-        // Apply the changes
-        {
-            if let Some(mimetype_manager) = self.mimetype_manager.borrow_mut().as_mut() {
-                for mimetype in &selected_mimetypes {
-                    if let Err(e) = mimetype_manager.set_default_app(mimetype, &desktop_file_name) {
-                        eprintln!("Failed to set default app for {}: {}", mimetype, e);
-                    } else {
-                        println!("Set {} as default for {}", desktop_file_name, mimetype);
-                    }
-                }
+        // Applications handling the role's types, those of this kind first
+        let mut candidates: Vec<&AppEntry> = desktop_manager
+            .get_entries()
+            .into_iter()
+            .filter(|entry| {
+                role.fits(&self.app_mimetypes(entry))
+                    || handlers.iter().any(|(app, _)| *app == entry.id)
+            })
+            .collect();
+        candidates.sort_by_cached_key(|entry| (!role.is_of_kind(entry), entry.name.to_lowercase()));
+
+        for entry in candidates {
+            let app_row = adw::ActionRow::builder()
+                .title(&entry.name)
+                .subtitle(&entry.id)
+                .use_markup(false)
+                .build();
+            app_row.add_prefix(&app_icon(entry.icon.as_deref()));
 
-                // Show a success message
-                println!(
-                    "Successfully applied changes for {} mimetypes",
-                    selected_mimetypes.len()
-                );
+            if handlers.len() == 1 && handlers[0].0 == entry.id {
+                app_row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
             } else {
-                eprintln!("Mimetype manager not initialized");
-                return;
+                let button = gtk::Button::builder()
+                    .label("Set as Default")
+                    .valign(gtk::Align::Center)
+                    .build();
+
+                let obj_weak = self.obj().downgrade();
+                let app = entry.id.clone();
+                button.connect_clicked(move |_| {
+                    if let Some(obj) = obj_weak.upgrade() {
+                        obj.imp().set_role_default(role, &app);
+                    }
+                });
+                app_row.add_suffix(&button);
             }
-        } // mimetype_manager borrow_mut ends here
+
+            row.add_row(&app_row);
+        }
+
+        // Keep the row open across refreshes
+        row.set_expanded(self.expanded_roles.borrow().contains(role.name));
+        let obj_weak = self.obj().downgrade();
+        row.connect_expanded_notify(move |row| {
+            if let Some(obj) = obj_weak.upgrade() {
+                let mut expanded_roles = obj.imp().expanded_roles.borrow_mut();
+                if row.is_expanded() {
+                    expanded_roles.insert(role.name);
+                } else {
+                    expanded_roles.remove(role.name);
+                }
+            }
+        });
+
+        row
+    }
+
+    // Makes `app` the default for the role's types it opens
+    fn set_role_default(&self, role: &Role, app: &str) {
+        let app_name = self.app_name(app);
+
+        let handled = self
+            .desktop_manager
+            .borrow()
+            .get_entry_by_desktop_file(app)
+            .map(|entry| self.app_mimetypes(entry))
+            .unwrap_or_default();
+        let mimetypes: Vec<&str> = role
+            .mimetypes
+            .iter()
+            .copied()
+            .filter(|mimetype| handled.iter().any(|handled| handled == mimetype))
+            .collect();
+        if mimetypes.is_empty() {
+            self.toast_overlay.add_toast(
+                adw::Toast::builder()
+                    .title(format!(
+                        "{} doesn't open any of the {} types",
+                        app_name,
+                        role.name.to_lowercase()
+                    ))
+                    .use_markup(false)
+                    .build(),
+            );
+            return;
+        }
+
+        let description = format!(
+            "Set {} as the default {}",
+            app_name,
+            role.name.to_lowercase()
+        );
+        self.stage(&description, |mimetype_manager| {
+            mimetype_manager.set_default_app_for_all(&mimetypes, app)
+        });
+        self.refresh_selected_app();
+        self.refresh_mimetypes_view();
+    }
+
+    /// Lists the URL schemes applications handle or have a default for,
+    /// after the default browser and the links opened by different
+    /// applications.
+    pub fn populate_links_view(&self) {
+        while let Some(child) = self.links_view_box.first_child() {
+            self.links_view_box.remove(&child);
+        }
+
+        let desktop_manager = self.desktop_manager.borrow();
+        let is_installed = |app: &str| desktop_manager.has_desktop_file(app);
+        let (split_handlers, browser) = match self.mimetype_manager.borrow().as_ref() {
+            Some(mimetype_manager) => (
+                mimetype_manager.get_split_handlers(is_installed),
+                mimetype_manager
+                    .get_effective_default(WEB_BROWSER_TYPES[0], is_installed)
+                    .map(|default| default.desktop_file),
+            ),
+            None => (Vec::new(), None),
+        };
+
+        if !split_handlers.is_empty() {
+            let group = adw::PreferencesGroup::builder()
+                .title("Opened by Different Applications")
+                .description("Links of the same kind don't open in the same application")
+                .build();
+
+            for split in split_handlers {
+                let subtitle = split
+                    .defaults
+                    .iter()
+                    .map(|(mimetype, app)| {
+                        format!(
+                            "{}: {}",
+                            scheme(mimetype).unwrap_or(mimetype),
+                            self.app_name(app)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" · ");
+                let row = adw::ActionRow::builder()
+                    .title(split.types.description)
+                    .subtitle(subtitle)
+                    .subtitle_lines(0)
+                    .use_markup(false)
+                    .build();
+                let icon = gtk::Image::from_icon_name("dialog-warning-symbolic");
+                icon.add_css_class("warning");
+                row.add_prefix(&icon);
+
+                // Settle on what the first type opens with
+                let app = split.defaults[0].1.clone();
+                let app_name = self.app_name(&app);
+                let button = gtk::Button::builder()
+                    .label(format!("Use {}", app_name))
+                    .tooltip_text("Open all of them with this application")
+                    .valign(gtk::Align::Center)
+                    .build();
+
+                let obj_weak = self.obj().downgrade();
+                let types = split.types;
+                button.connect_clicked(move |_| {
+                    let Some(obj) = obj_weak.upgrade() else {
+                        return;
+                    };
+                    let imp = obj.imp();
+
+                    let description = format!(
+                        "Open {} with {}",
+                        types.description.to_lowercase(),
+                        app_name
+                    );
+                    imp.stage(&description, |mimetype_manager| {
+                        mimetype_manager.set_default_app_for_all(types.mimetypes, &app)
+                    });
+                    imp.refresh_mimetypes_view();
+                    imp.refresh_selected_app();
+                });
+                row.add_suffix(&button);
+
+                group.add(&row);
+            }
+            self.links_view_box.append(&group);
+        }
+
+        let browser_group = adw::PreferencesGroup::builder()
+            .title("Web Browser")
+            .build();
+        let browser_row = adw::ActionRow::builder()
+            .title("Default Browser")
+            .subtitle(
+                browser
+                    .as_deref()
+                    .map_or("None".to_string(), |app| self.app_name(app)),
+            )
+            .use_markup(false)
+            .build();
+        browser_row.add_prefix(&app_icon(
+            browser
+                .as_deref()
+                .and_then(|app| desktop_manager.get_entry_by_desktop_file(app))
+                .and_then(|entry| entry.icon.as_deref()),
+        ));
+        browser_row.add_suffix(
+            &gtk::Button::builder()
+                .label("Change…")
+                .tooltip_text("Set the browser for web links and pages at once")
+                .valign(gtk::Align::Center)
+                .action_name("win.choose-browser")
+                .build(),
+        );
+        browser_group.add(&browser_row);
+        self.links_view_box.append(&browser_group);
+
+        let mut mimetypes: BTreeSet<String> =
+            desktop_manager.get_all_mimetypes().into_iter().collect();
+        if let Some(mimetype_manager) = self.mimetype_manager.borrow().as_ref() {
+            mimetypes.extend(mimetype_manager.get_all_mimetypes());
+        }
+
+        let protocols_group = adw::PreferencesGroup::builder()
+            .title("Protocols")
+            .description("Links applications open, like mailto: or steam:")
+            .build();
+        for mimetype in mimetypes
+            .iter()
+            .filter(|mimetype| scheme(mimetype).is_some())
+        {
+            protocols_group.add(&self.mimetype_view_row(mimetype));
+        }
+        self.links_view_box.append(&protocols_group);
+    }
+
+    // Lets the user pick the application opening web links and pages
+    fn show_browser_dialog(&self) {
+        let desktop_manager = self.desktop_manager.borrow();
+        let current = self.mimetype_manager.borrow().as_ref().and_then(|manager| {
+            manager
+                .get_effective_default(WEB_BROWSER_TYPES[0], |app| {
+                    desktop_manager.has_desktop_file(app)
+                })
+                .map(|default| default.desktop_file)
+        });
+
+        // Applications opening web links, or calling themselves browsers
+        let mut browsers: Vec<&AppEntry> = desktop_manager
+            .get_entries()
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .categories
+                    .iter()
+                    .any(|category| category == "WebBrowser")
+                    || entry
+                        .mimetypes
+                        .iter()
+                        .any(|mimetype| WEB_BROWSER_TYPES[..2].contains(&mimetype.as_str()))
+            })
+            .collect();
+        browsers.sort_by_cached_key(|entry| entry.name.to_lowercase());
+
+        let dialog = adw::Dialog::builder()
+            .title("Default Browser")
+            .content_width(420)
+            .content_height(420)
+            .build();
+
+        let content: gtk::Widget = if browsers.is_empty() {
+            adw::StatusPage::builder()
+                .icon_name("web-browser-symbolic")
+                .title("No Browsers Found")
+                .description("No installed application opens web links")
+                .build()
+                .upcast()
+        } else {
+            let list_box = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .margin_start(12)
+                .margin_end(12)
+                .margin_top(12)
+                .margin_bottom(12)
+                .valign(gtk::Align::Start)
+                .build();
+            list_box.add_css_class("boxed-list");
+
+            for entry in browsers {
+                let row = adw::ActionRow::builder()
+                    .title(&entry.name)
+                    .subtitle(&entry.id)
+                    .use_markup(false)
+                    .activatable(true)
+                    .build();
+                row.add_prefix(&app_icon(entry.icon.as_deref()));
+                if current.as_deref() == Some(entry.id.as_str()) {
+                    row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
+                }
+
+                let obj_weak = self.obj().downgrade();
+                let dialog_weak = dialog.downgrade();
+                let app = entry.id.clone();
+                let description = format!("Set {} as the default browser", entry.name);
+                row.connect_activated(move |_| {
+                    let Some(obj) = obj_weak.upgrade() else {
+                        return;
+                    };
+                    let imp = obj.imp();
+
+                    imp.stage(&description, |mimetype_manager| {
+                        mimetype_manager.set_default_browser(&app)
+                    });
+
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.close();
+                    }
+                    imp.refresh_mimetypes_view();
+                    imp.refresh_selected_app();
+                });
+
+                list_box.append(&row);
+            }
+
+            gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .child(&list_box)
+                .build()
+                .upcast()
+        };
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    fn mimetype_view_row(&self, mimetype: &str) -> adw::ExpanderRow {
+        let desktop_manager = self.desktop_manager.borrow();
+        let mime_database = self.mime_database.borrow();
+
+        let default_app = self.mimetype_manager.borrow().as_ref().and_then(|manager| {
+            manager.get_inherited_default(mimetype, &mime_database, |app| {
+                desktop_manager.has_desktop_file(app)
+            })
+        });
+        let subtitle = match &default_app {
+            Some(default) => {
+                let name = desktop_manager
+                    .get_entry_by_desktop_file(&default.desktop_file)
+                    .map_or(default.desktop_file.clone(), |entry| entry.name.clone());
+                if default.mimetype == mimetype {
+                    format!("{} → {}", mimetype, name)
+                } else {
+                    format!(
+                        "{} → {} (inherited from {})",
+                        mimetype, name, default.mimetype
+                    )
+                }
+            }
+            None => format!("{} → No default set", mimetype),
+        };
+
+        // URL schemes aren't in the MIME database
+        let (title, icon_name) = match scheme(mimetype) {
+            Some(scheme) => (scheme_name(scheme), "insert-link-symbolic".to_string()),
+            None => (
+                mime_database
+                    .description(mimetype)
+                    .unwrap_or_else(|| mimetype.to_string()),
+                mime_database.icon_name(mimetype),
+            ),
+        };
+        let row = adw::ExpanderRow::builder()
+            .title(title)
+            .subtitle(subtitle)
+            .use_markup(false)
+            .build();
+        row.add_prefix(&gtk::Image::from_icon_name(&icon_name));
+
+        // There are hundreds of types, so only list applications on demand
+        let expanded = self.expanded_mimetypes.borrow().contains(mimetype);
+        let filled = Rc::new(Cell::new(false));
+        let obj_weak = self.obj().downgrade();
+        let mimetype = mimetype.to_string();
+        let default_app = default_app.map(|default| default.desktop_file);
+        row.connect_expanded_notify(move |row| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+
+            if row.is_expanded() {
+                imp.expanded_mimetypes.borrow_mut().insert(mimetype.clone());
+                if !filled.replace(true) {
+                    imp.fill_mimetype_view_row(row, &mimetype, default_app.as_deref());
+                }
+            } else {
+                imp.expanded_mimetypes.borrow_mut().remove(&mimetype);
+            }
+        });
+
+        // Set after connecting, so the applications get listed
+        row.set_expanded(expanded);
+
+        row
+    }
+
+    fn fill_mimetype_view_row(
+        &self,
+        row: &adw::ExpanderRow,
+        mimetype: &str,
+        default_app: Option<&str>,
+    ) {
+        let desktop_manager = self.desktop_manager.borrow();
+        let declared = desktop_manager
+            .get_entries_for_mimetype(mimetype)
+            .into_iter()
+            .map(|entry| entry.id.clone());
+        let apps = match self.mimetype_manager.borrow().as_ref() {
+            Some(mimetype_manager) => mimetype_manager.get_associated_apps(mimetype, declared),
+            None => declared.collect(),
+        };
+
+        for app in apps {
+            let Some(entry) = desktop_manager.get_entry_by_desktop_file(&app) else {
+                continue;
+            };
+
+            let app_row = adw::ActionRow::builder()
+                .title(&entry.name)
+                .subtitle(&app)
+                .use_markup(false)
+                .build();
+            app_row.add_prefix(&app_icon(entry.icon.as_deref()));
+
+            if default_app == Some(app.as_str()) {
+                app_row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
+            } else {
+                let button = gtk::Button::builder()
+                    .label("Set as Default")
+                    .valign(gtk::Align::Center)
+                    .build();
+
+                let obj_weak = self.obj().downgrade();
+                let mimetype = mimetype.to_string();
+                button.connect_clicked(move |_| {
+                    if let Some(obj) = obj_weak.upgrade() {
+                        obj.imp().set_default_from_mimetypes_view(&mimetype, &app);
+                    }
+                });
+                app_row.add_suffix(&button);
+            }
+
+            row.add_row(&app_row);
+        }
+    }
+
+    fn set_default_from_mimetypes_view(&self, mimetype: &str, app: &str) {
+        let description = format!("Set {} as default for {}", self.app_name(app), mimetype);
+        self.stage(&description, |mimetype_manager| {
+            mimetype_manager.set_default_app(mimetype, app)
+        });
+
+        self.refresh_mimetypes_view();
+        self.refresh_selected_app();
+    }
+
+    // Rebuilds the roles, MIME types or links view, whichever is shown, which
+    // would otherwise scroll back to the top
+    fn refresh_mimetypes_view(&self) {
+        let (scrolled_window, populate): (&gtk::ScrolledWindow, fn(&Self)) =
+            match self.view_stack.visible_child_name().as_deref() {
+                Some("mimetypes") => (
+                    &self.mimetypes_scrolled_window,
+                    Self::populate_mimetypes_view,
+                ),
+                Some("links") => (&self.links_scrolled_window, Self::populate_links_view),
+                Some("roles") => (&self.roles_scrolled_window, Self::populate_roles_view),
+                _ => return,
+            };
+
+        let adjustment = scrolled_window.vadjustment();
+        let scroll_position = adjustment.value();
+        populate(self);
+        glib::idle_add_local_once(move || adjustment.set_value(scroll_position));
+    }
+
+    // A flat icon button running `on_clicked`
+    fn association_button<F>(&self, icon_name: &str, tooltip: &str, on_clicked: F) -> gtk::Button
+    where
+        F: Fn(&Self) + 'static,
+    {
+        let button = gtk::Button::builder()
+            .icon_name(icon_name)
+            .tooltip_text(tooltip)
+            .valign(gtk::Align::Center)
+            .build();
+        button.add_css_class("flat");
+
+        let obj_weak = self.obj().downgrade();
+        button.connect_clicked(move |_| {
+            if let Some(obj) = obj_weak.upgrade() {
+                on_clicked(obj.imp());
+            }
+        });
+
+        button
+    }
+
+    fn setup_default_reordering(
+        &self,
+        row: &adw::ActionRow,
+        mimetype: &str,
+        app: &str,
+        position: usize,
+        mimetypes: &[String],
+    ) {
+        let drag_source = gtk::DragSource::builder()
+            .actions(gdk::DragAction::MOVE)
+            .build();
+
+        // Carry the mimetype along, so defaults can't be dropped on another type
+        let payload = format!("{}\n{}", mimetype, app);
+        drag_source.connect_prepare(move |_, _, _| {
+            Some(gdk::ContentProvider::for_value(&payload.to_value()))
+        });
+
+        let row_weak = row.downgrade();
+        drag_source.connect_drag_begin(move |drag_source, _| {
+            if let Some(row) = row_weak.upgrade() {
+                let icon = gtk::WidgetPaintable::new(Some(&row));
+                drag_source.set_icon(Some(&icon), 0, 0);
+            }
+        });
+        row.add_controller(drag_source);
+
+        let drop_target = gtk::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
+        let obj_weak = self.obj().downgrade();
+        let (mimetype, mimetypes) = (mimetype.to_string(), mimetypes.to_vec());
+        drop_target.connect_drop(move |_, value, _, _| {
+            let Ok(payload) = value.get::<String>() else {
+                return false;
+            };
+            let Some((dragged_mimetype, dragged_app)) = payload.split_once('\n') else {
+                return false;
+            };
+            if dragged_mimetype != mimetype {
+                return false;
+            }
+
+            // Rebuild the rows once the drop has finished, not from inside it
+            let obj_weak = obj_weak.clone();
+            let (mimetype, mimetypes) = (mimetype.clone(), mimetypes.clone());
+            let dragged_app = dragged_app.to_string();
+            glib::idle_add_local_once(move || {
+                if let Some(obj) = obj_weak.upgrade() {
+                    let description = format!("Reorder the defaults of {}", mimetype);
+                    obj.imp()
+                        .update_associations(&mimetypes, &description, |manager| {
+                            manager.move_default_app(&mimetype, &dragged_app, position)
+                        });
+                }
+            });
+            true
+        });
+        row.add_controller(drop_target);
+    }
+
+    fn update_associations<F>(&self, mimetypes: &[String], description: &str, update: F)
+    where
+        F: FnOnce(&mut MimetypeManager),
+    {
+        self.stage(description, update);
+        self.populate_active_mimetypes(mimetypes);
+    }
+
+    // Applies `update` to the staged changes, to be reviewed and saved later,
+    // as one step of the history
+    fn stage<F>(&self, description: &str, update: F)
+    where
+        F: FnOnce(&mut MimetypeManager),
+    {
+        match self.mimetype_manager.borrow_mut().as_mut() {
+            Some(mimetype_manager) => mimetype_manager.record(description, update),
+            None => {
+                eprintln!("Mimetype manager not initialized");
+                return;
+            }
+        }
+        self.update_changes_banner();
+        self.show_undo_toast(description);
+
+        // Type counts follow the associations
+        self.populate_apps_list();
+    }
+
+    fn show_undo_toast(&self, description: &str) {
+        let toast = adw::Toast::builder()
+            .title(description)
+            .button_label("Undo")
+            .action_name("win.undo")
+            .use_markup(false)
+            .build();
+        self.toast_overlay.add_toast(toast);
+    }
+
+    fn app_name(&self, desktop_file: &str) -> String {
+        self.desktop_manager
+            .borrow()
+            .get_any_entry(desktop_file)
+            .map_or_else(|| desktop_file.to_string(), |entry| entry.name.clone())
+    }
+
+    // Reverts the latest change, or applies the earliest undone one again
+    fn undo_or_redo(&self, redo: bool) {
+        let description = match self.mimetype_manager.borrow_mut().as_mut() {
+            Some(mimetype_manager) if redo => mimetype_manager.redo(),
+            Some(mimetype_manager) => mimetype_manager.undo(),
+            None => None,
+        };
+        let Some(description) = description else {
+            return;
+        };
+
+        self.update_changes_banner();
+        self.refresh_after_reload();
+        let title = if redo {
+            format!("Redone: {}", description)
+        } else {
+            format!("Undone: {}", description)
+        };
+        self.toast_overlay
+            .add_toast(adw::Toast::builder().title(title).use_markup(false).build());
+    }
+
+    fn has_pending_changes(&self) -> bool {
+        self.mimetype_manager
+            .borrow()
+            .as_ref()
+            .is_some_and(MimetypeManager::has_pending_changes)
+    }
+
+    // Shows the banner while there are unsaved changes
+    fn update_changes_banner(&self) {
+        let count = self
+            .mimetype_manager
+            .borrow()
+            .as_ref()
+            .map_or(0, |manager| manager.pending_changes().len());
+
+        let obj = self.obj();
+        obj.action_set_enabled("win.save-changes", count > 0);
+        obj.action_set_enabled("win.discard-changes", count > 0);
+
+        let (can_undo, can_redo) = self
+            .mimetype_manager
+            .borrow()
+            .as_ref()
+            .map_or((false, false), |manager| {
+                (manager.can_undo(), manager.can_redo())
+            });
+        obj.action_set_enabled("win.undo", can_undo);
+        obj.action_set_enabled("win.redo", can_redo);
+
+        self.changes_banner.set_title(&if count == 1 {
+            "1 unsaved change".to_string()
+        } else {
+            format!("{} unsaved changes", count)
+        });
+        self.changes_banner.set_revealed(count > 0);
+    }
+
+    // Writes the staged changes, asking what to do if another program
+    // changed the file meanwhile
+    fn save_changes(&self) {
+        let result = match self.mimetype_manager.borrow_mut().as_mut() {
+            Some(mimetype_manager) => mimetype_manager.save(),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.handle_save_error("Failed to save mimeapps.list", &*e);
+        }
+        self.update_changes_banner();
+    }
+
+    fn discard_changes(&self) {
+        if let Some(mimetype_manager) = self.mimetype_manager.borrow_mut().as_mut()
+            && let Err(e) = mimetype_manager.discard_changes()
+        {
+            eprintln!("Failed to reload mimeapps.list: {}", e);
+        }
+        self.update_changes_banner();
+        self.refresh_after_reload();
+    }
+
+    // Shows the associations again after they changed all at once
+    fn refresh_after_reload(&self) {
+        self.populate_apps_list();
+        self.refresh_selected_app();
+        self.refresh_mimetypes_view();
+    }
+
+    pub fn show_review_dialog(&self) {
+        let changes = match self.mimetype_manager.borrow().as_ref() {
+            Some(mimetype_manager) => mimetype_manager.pending_changes(),
+            None => return,
+        };
+
+        let dialog = adw::Dialog::builder()
+            .title("Unsaved Changes")
+            .content_width(560)
+            .content_height(480)
+            .build();
+
+        let content: gtk::Widget = if changes.is_empty() {
+            adw::StatusPage::builder()
+                .icon_name("emblem-ok-symbolic")
+                .title("No Unsaved Changes")
+                .description("Everything is saved to mimeapps.list")
+                .build()
+                .upcast()
+        } else {
+            let list_box = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .margin_start(12)
+                .margin_end(12)
+                .margin_top(12)
+                .margin_bottom(12)
+                .valign(gtk::Align::Start)
+                .build();
+            list_box.add_css_class("boxed-list");
+
+            let mime_database = self.mime_database.borrow();
+            for change in &changes {
+                let row = adw::ActionRow::builder()
+                    .title(
+                        mime_database
+                            .description(&change.mimetype)
+                            .unwrap_or_else(|| change.mimetype.clone()),
+                    )
+                    .subtitle(format!(
+                        "{} · {}",
+                        change.mimetype,
+                        self.describe_change(change)
+                    ))
+                    .subtitle_lines(0)
+                    .use_markup(false)
+                    .build();
+                row.add_prefix(&gtk::Image::from_icon_name(
+                    &mime_database.icon_name(&change.mimetype),
+                ));
+                list_box.append(&row);
+            }
+
+            gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .child(&list_box)
+                .build()
+                .upcast()
+        };
+
+        let discard_button = gtk::Button::with_label("Discard");
+        discard_button.add_css_class("destructive-action");
+        let save_button = gtk::Button::with_label("Save");
+        save_button.add_css_class("suggested-action");
+        discard_button.set_sensitive(!changes.is_empty());
+        save_button.set_sensitive(!changes.is_empty());
+
+        for (button, save) in [(&discard_button, false), (&save_button, true)] {
+            let obj_weak = self.obj().downgrade();
+            let dialog_weak = dialog.downgrade();
+            button.connect_clicked(move |_| {
+                let Some(obj) = obj_weak.upgrade() else {
+                    return;
+                };
+                if let Some(dialog) = dialog_weak.upgrade() {
+                    dialog.close();
+                }
+                if save {
+                    obj.imp().save_changes();
+                } else {
+                    obj.imp().discard_changes();
+                }
+            });
+        }
+
+        let actions = gtk::Box::builder()
+            .spacing(12)
+            .halign(gtk::Align::End)
+            .margin_start(12)
+            .margin_end(12)
+            .margin_top(12)
+            .margin_bottom(12)
+            .build();
+        actions.append(&discard_button);
+        actions.append(&save_button);
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.add_bottom_bar(&actions);
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    pub fn show_history_dialog(&self) {
+        let history = match self.mimetype_manager.borrow().as_ref() {
+            Some(mimetype_manager) => mimetype_manager.history().to_vec(),
+            None => return,
+        };
+
+        let dialog = adw::Dialog::builder()
+            .title("History")
+            .content_width(560)
+            .content_height(520)
+            .build();
+
+        let content: gtk::Widget = if history.is_empty() {
+            adw::StatusPage::builder()
+                .icon_name("document-open-recent-symbolic")
+                .title("No Changes Yet")
+                .description("Saved changes to the default applications are listed here")
+                .build()
+                .upcast()
+        } else {
+            let list_box = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .margin_start(12)
+                .margin_end(12)
+                .margin_top(12)
+                .margin_bottom(12)
+                .valign(gtk::Align::Start)
+                .build();
+            list_box.add_css_class("boxed-list");
+
+            // Newest first
+            for entry in history.iter().rev() {
+                let time = i64::try_from(entry.time)
+                    .ok()
+                    .and_then(|time| glib::DateTime::from_unix_local(time).ok())
+                    .and_then(|time| time.format("%x %X").ok())
+                    .map(|time| time.to_string())
+                    .unwrap_or_default();
+                let subtitle = if entry.undone {
+                    format!("{} · Undone", time)
+                } else {
+                    time
+                };
+
+                let row = adw::ExpanderRow::builder()
+                    .title(&entry.description)
+                    .subtitle(subtitle)
+                    .use_markup(false)
+                    .build();
+
+                for change in &entry.changes {
+                    row.add_row(
+                        &adw::ActionRow::builder()
+                            .title(&change.mimetype)
+                            .subtitle(self.describe_change(change))
+                            .subtitle_lines(0)
+                            .use_markup(false)
+                            .build(),
+                    );
+                }
+
+                let revert_button = gtk::Button::builder()
+                    .label("Revert")
+                    .tooltip_text("Put back what this change replaced")
+                    .valign(gtk::Align::Center)
+                    .sensitive(!entry.undone)
+                    .build();
+
+                let id = entry.id;
+                let obj_weak = self.obj().downgrade();
+                let dialog_weak = dialog.downgrade();
+                revert_button.connect_clicked(move |_| {
+                    let Some(obj) = obj_weak.upgrade() else {
+                        return;
+                    };
+                    let imp = obj.imp();
+
+                    if let Some(mimetype_manager) = imp.mimetype_manager.borrow_mut().as_mut() {
+                        mimetype_manager.revert(id);
+                    }
+
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.close();
+                    }
+                    imp.update_changes_banner();
+                    imp.refresh_after_reload();
+                });
+
+                row.add_suffix(&revert_button);
+                list_box.append(&row);
+            }
+
+            gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .child(&list_box)
+                .build()
+                .upcast()
+        };
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    // `Default applications: Firefox → Chromium, Firefox`
+    fn describe_change(&self, change: &PendingChange) -> String {
+        format!(
+            "{}: {} → {}",
+            change.kind,
+            self.describe_apps(&change.old),
+            self.describe_apps(&change.new)
+        )
+    }
+
+    // `Firefox, Chromium`, or `none`
+    fn describe_apps(&self, apps: &[String]) -> String {
+        if apps.is_empty() {
+            return "none".to_string();
+        }
+        apps.iter()
+            .map(|app| self.app_name(app))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn show_backups_dialog(&self) {
+        let backups = match self.mimetype_manager.borrow().as_ref() {
+            Some(mimetype_manager) => mimetype_manager.list_backups(),
+            None => {
+                eprintln!("Mimetype manager not initialized");
+                return;
+            }
+        };
+
+        let dialog = adw::Dialog::builder()
+            .title("Restore Backup")
+            .content_width(460)
+            .content_height(420)
+            .build();
+
+        let content: gtk::Widget = if backups.is_empty() {
+            adw::StatusPage::builder()
+                .icon_name("document-open-recent-symbolic")
+                .title("No Backups")
+                .description("A backup is taken every time mimeapps.list is saved")
+                .build()
+                .upcast()
+        } else {
+            let list_box = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .margin_start(12)
+                .margin_end(12)
+                .margin_top(12)
+                .margin_bottom(12)
+                .valign(gtk::Align::Start)
+                .build();
+            list_box.add_css_class("boxed-list");
+
+            for backup in backups {
+                let row = adw::ActionRow::builder()
+                    .title(&backup.created)
+                    .subtitle(backup.path.display().to_string())
+                    .use_markup(false)
+                    .build();
+
+                let restore_button = gtk::Button::builder()
+                    .label("Restore")
+                    .valign(gtk::Align::Center)
+                    .build();
+
+                let obj_weak = self.obj().downgrade();
+                let dialog_weak = dialog.downgrade();
+                restore_button.connect_clicked(move |_| {
+                    let Some(obj) = obj_weak.upgrade() else {
+                        return;
+                    };
+                    let imp = obj.imp();
+
+                    let result = match imp.mimetype_manager.borrow_mut().as_mut() {
+                        Some(mimetype_manager) => mimetype_manager.restore_backup(&backup),
+                        None => return,
+                    };
+                    let title = match result {
+                        Ok(()) => format!("Restored the backup of {}", backup.created),
+                        Err(e) => {
+                            eprintln!("Failed to restore backup: {}", e);
+                            format!("Failed to restore the backup: {}", e)
+                        }
+                    };
+
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.close();
+                    }
+                    imp.toast_overlay
+                        .add_toast(adw::Toast::builder().title(title).use_markup(false).build());
+                    imp.update_changes_banner();
+                    imp.refresh_after_reload();
+                });
+
+                row.add_suffix(&restore_button);
+                list_box.append(&row);
+            }
+
+            gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .child(&list_box)
+                .build()
+                .upcast()
+        };
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    fn choose_file(&self) {
+        let obj_weak = self.obj().downgrade();
+        gtk::FileDialog::builder()
+            .title("What Opens This File?")
+            .modal(true)
+            .build()
+            .open(Some(&*self.obj()), gio::Cancellable::NONE, move |result| {
+                if let Some(obj) = obj_weak.upgrade()
+                    && let Ok(file) = result
+                    && let Some(path) = file.path()
+                {
+                    obj.imp().show_file_handler_dialog(&path);
+                }
+            });
+    }
+
+    fn setup_file_drop(&self) {
+        let drop_target = gtk::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+
+        let obj_weak = self.obj().downgrade();
+        drop_target.connect_drop(move |_, value, _, _| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return false;
+            };
+            let Some(path) = value
+                .get::<gdk::FileList>()
+                .ok()
+                .and_then(|files| files.files().first()?.path())
+            else {
+                return false;
+            };
+
+            obj.imp().show_file_handler_dialog(&path);
+            true
+        });
+
+        self.obj().add_controller(drop_target);
+    }
+
+    /// Shows the type of the file at `path`, the application opening it and
+    /// the other applications able to.
+    pub fn show_file_handler_dialog(&self, path: &Path) {
+        let mimetype = self.mime_database.borrow().mimetype_for_file(path);
+
+        let dialog = adw::Dialog::builder()
+            .title("What Opens This File?")
+            .content_width(460)
+            .content_height(520)
+            .build();
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&self.file_handler_page(
+            path,
+            &mimetype,
+            &toolbar_view,
+        )));
+        dialog.set_child(Some(&toolbar_view));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    fn file_handler_page(
+        &self,
+        path: &Path,
+        mimetype: &str,
+        toolbar_view: &adw::ToolbarView,
+    ) -> adw::PreferencesPage {
+        let desktop_manager = self.desktop_manager.borrow();
+        let mime_database = self.mime_database.borrow();
+        let mimetype_manager = self.mimetype_manager.borrow();
+
+        let page = adw::PreferencesPage::new();
+        let file_group = adw::PreferencesGroup::new();
+        page.add(&file_group);
+
+        let file_row = adw::ActionRow::builder()
+            .title(path.file_name().map_or(path.display().to_string(), |name| {
+                name.to_string_lossy().to_string()
+            }))
+            .subtitle(format!(
+                "{} ({})",
+                mime_database
+                    .description(mimetype)
+                    .unwrap_or_else(|| mimetype.to_string()),
+                mimetype
+            ))
+            .use_markup(false)
+            .build();
+        file_row.add_prefix(&gtk::Image::from_icon_name(
+            &mime_database.icon_name(mimetype),
+        ));
+        file_group.add(&file_row);
+
+        let Some(mimetype_manager) = mimetype_manager.as_ref() else {
+            eprintln!("Mimetype manager not initialized");
+            return page;
+        };
+
+        let handler = mimetype_manager.get_inherited_default(mimetype, &mime_database, |app| {
+            desktop_manager.has_desktop_file(app)
+        });
+        let handler_row = adw::ActionRow::builder()
+            .title("Opens with")
+            .use_markup(false)
+            .build();
+        match &handler {
+            Some(handler) => {
+                let name = desktop_manager
+                    .get_entry_by_desktop_file(&handler.desktop_file)
+                    .map_or(handler.desktop_file.clone(), |entry| entry.name.clone());
+                let inherited = if handler.mimetype == mimetype {
+                    String::new()
+                } else {
+                    format!(", inherited from {}", handler.mimetype)
+                };
+                handler_row.set_subtitle(&format!(
+                    "{} (set in {}{})",
+                    name,
+                    handler.source.display(),
+                    inherited
+                ));
+            }
+            None => handler_row.set_subtitle("No default set"),
+        }
+        file_group.add(&handler_row);
+
+        let apps_group = adw::PreferencesGroup::builder()
+            .title("Applications")
+            .build();
+        page.add(&apps_group);
+
+        let declared = desktop_manager
+            .get_entries_for_mimetype(mimetype)
+            .into_iter()
+            .map(|entry| entry.id.clone());
+        let apps = mimetype_manager.get_associated_apps(mimetype, declared);
+        if apps.is_empty() {
+            apps_group.set_description(Some("No installed application declares this type"));
+        }
+
+        for app in apps {
+            let Some(entry) = desktop_manager.get_entry_by_desktop_file(&app) else {
+                continue;
+            };
+
+            let row = adw::ActionRow::builder()
+                .title(&entry.name)
+                .subtitle(&app)
+                .use_markup(false)
+                .build();
+            row.add_prefix(&app_icon(entry.icon.as_deref()));
+
+            if handler
+                .as_ref()
+                .is_some_and(|handler| handler.desktop_file == app)
+            {
+                row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
+            } else {
+                let button = gtk::Button::builder()
+                    .label("Set as Default")
+                    .valign(gtk::Align::Center)
+                    .build();
+
+                let obj_weak = self.obj().downgrade();
+                let toolbar_view_weak = toolbar_view.downgrade();
+                let (path, mimetype) = (path.to_path_buf(), mimetype.to_string());
+                button.connect_clicked(move |_| {
+                    let Some(obj) = obj_weak.upgrade() else {
+                        return;
+                    };
+                    let imp = obj.imp();
+
+                    let description =
+                        format!("Set {} as default for {}", imp.app_name(&app), mimetype);
+                    imp.stage(&description, |mimetype_manager| {
+                        mimetype_manager.set_default_app(&mimetype, &app)
+                    });
+
+                    if let Some(toolbar_view) = toolbar_view_weak.upgrade() {
+                        let page = imp.file_handler_page(&path, &mimetype, &toolbar_view);
+                        toolbar_view.set_content(Some(&page));
+                    }
+                    imp.refresh_selected_app();
+                });
+                row.add_suffix(&button);
+            }
+
+            apps_group.add(&row);
+        }
+
+        page
+    }
+
+    /// Lets the user pick any MIME type for the selected application, with
+    /// suggestions from the MIME database.
+    pub fn show_add_mimetype_dialog(&self) {
+        let Some(app_id) = self.selected_app_id.borrow().clone() else {
+            return;
+        };
+
+        // Every type we know about, to suggest from
+        let mut known_mimetypes: BTreeSet<String> = self
+            .mime_database
+            .borrow()
+            .get_all_mimetypes()
+            .into_iter()
+            .collect();
+        known_mimetypes.extend(self.desktop_manager.borrow().get_all_mimetypes());
+        if let Some(mimetype_manager) = self.mimetype_manager.borrow().as_ref() {
+            known_mimetypes.extend(mimetype_manager.get_all_mimetypes());
+        }
+        let known_mimetypes: Vec<String> = known_mimetypes.into_iter().collect();
+
+        let dialog = adw::Dialog::builder()
+            .title("Add MIME Type")
+            .content_width(460)
+            .content_height(520)
+            .build();
+
+        let search_entry = gtk::SearchEntry::builder()
+            .placeholder_text("e.g. video/mp4 or x-scheme-handler/mailto")
+            .build();
+        let make_default = gtk::CheckButton::builder()
+            .label("Make it the default application")
+            .active(true)
+            .build();
+        let suggestions = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .valign(gtk::Align::Start)
+            .build();
+        suggestions.add_css_class("boxed-list");
+
+        // Adds `mimetype` to the app and closes the dialog
+        let add = {
+            let obj_weak = self.obj().downgrade();
+            let dialog_weak = dialog.downgrade();
+            let make_default = make_default.clone();
+            Rc::new(move |mimetype: &str| {
+                let Some(obj) = obj_weak.upgrade() else {
+                    return;
+                };
+                let imp = obj.imp();
+
+                let description = if make_default.is_active() {
+                    format!("Set {} as default for {}", imp.app_name(&app_id), mimetype)
+                } else {
+                    format!("Associate {} with {}", imp.app_name(&app_id), mimetype)
+                };
+                imp.stage(&description, |mimetype_manager| {
+                    mimetype_manager.add_association(mimetype, &app_id);
+                    if make_default.is_active() {
+                        mimetype_manager.set_default_app(mimetype, &app_id);
+                    }
+                });
+
+                if let Some(dialog) = dialog_weak.upgrade() {
+                    dialog.close();
+                }
+                imp.refresh_selected_app();
+            })
+        };
+
+        let obj_weak = self.obj().downgrade();
+        let suggestions_weak = suggestions.downgrade();
+        let suggest = {
+            let add = add.clone();
+            move |query: &str| {
+                let (Some(obj), Some(suggestions)) =
+                    (obj_weak.upgrade(), suggestions_weak.upgrade())
+                else {
+                    return;
+                };
+                suggestions.remove_all();
+
+                let query = query.trim().to_lowercase();
+                if query.is_empty() {
+                    return;
+                }
+
+                let mime_database = obj.imp().mime_database.borrow();
+                let matches = known_mimetypes
+                    .iter()
+                    .map(|mimetype| (mimetype, mime_database.description(mimetype)))
+                    .filter(|(mimetype, description)| {
+                        mimetype.contains(&query)
+                            || description.as_ref().is_some_and(|description| {
+                                description.to_lowercase().contains(&query)
+                            })
+                    })
+                    .take(50);
+
+                for (mimetype, description) in matches {
+                    let row = adw::ActionRow::builder()
+                        .title(description.unwrap_or_else(|| mimetype.clone()))
+                        .subtitle(mimetype)
+                        .use_markup(false)
+                        .activatable(true)
+                        .build();
+                    row.add_prefix(&gtk::Image::from_icon_name(
+                        &mime_database.icon_name(mimetype),
+                    ));
+
+                    let add = add.clone();
+                    let mimetype = mimetype.clone();
+                    row.connect_activated(move |_| add(&mimetype));
+                    suggestions.append(&row);
+                }
+            }
+        };
+        search_entry.connect_search_changed(move |entry| suggest(&entry.text()));
+
+        // Enter adds what was typed, or the best suggestion
+        let suggestions_weak = suggestions.downgrade();
+        search_entry.connect_activate(move |entry| {
+            let text = entry.text().trim().to_string();
+            if is_valid_mimetype(&text) {
+                add(&text);
+            } else if let Some(row) = suggestions_weak
+                .upgrade()
+                .and_then(|suggestions| suggestions.row_at_index(0))
+            {
+                row.activate();
+            }
+        });
+
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .margin_start(12)
+            .margin_end(12)
+            .margin_top(12)
+            .margin_bottom(12)
+            .build();
+        content.append(&search_entry);
+        content.append(&make_default);
+        content.append(
+            &gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .vexpand(true)
+                .child(&suggestions)
+                .build(),
+        );
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+        dialog.set_focus(Some(&search_entry));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    fn edit_selected_launcher(&self) {
+        let path = {
+            let desktop_manager = self.desktop_manager.borrow();
+            self.selected_app_id
+                .borrow()
+                .as_ref()
+                .and_then(|app_id| desktop_manager.get_any_entry(app_id))
+                .map(|entry| entry.path.clone())
+        };
+        let Some(path) = path.filter(|path| launcher::is_user_launcher(path)) else {
+            return;
+        };
+
+        match Launcher::load(&path) {
+            Ok(launcher) => self.show_launcher_dialog(launcher),
+            Err(e) => eprintln!("Failed to read {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn show_launcher_dialog(&self, launcher: Launcher) {
+        let is_new = launcher.path.is_none();
+        let dialog = adw::Dialog::builder()
+            .title(if is_new {
+                "New Launcher"
+            } else {
+                "Edit Launcher"
+            })
+            .content_width(480)
+            .build();
+
+        let name_row = adw::EntryRow::builder()
+            .title("Name")
+            .text(launcher.name.as_str())
+            .build();
+        let exec_row = adw::EntryRow::builder()
+            .title("Command, e.g. myeditor %F")
+            .text(launcher.exec.as_str())
+            .build();
+        let icon_row = adw::EntryRow::builder()
+            .title("Icon Name or Path")
+            .text(launcher.icon.as_str())
+            .build();
+        let terminal_row = adw::SwitchRow::builder()
+            .title("Run in Terminal")
+            .active(launcher.terminal)
+            .build();
+
+        let launcher_group = adw::PreferencesGroup::new();
+        launcher_group.add(&name_row);
+        launcher_group.add(&exec_row);
+        launcher_group.add(&icon_row);
+        launcher_group.add(&terminal_row);
+
+        let mimetypes_row = adw::EntryRow::builder()
+            .title("MIME Types, separated by ;")
+            .text(launcher.mimetypes.join(";"))
+            .build();
+        let make_default_row = adw::SwitchRow::builder()
+            .title("Make It the Default Application")
+            .subtitle("For each of these MIME types")
+            .active(is_new)
+            .build();
+
+        let mimetypes_group = adw::PreferencesGroup::builder().title("Opens").build();
+        mimetypes_group.add(&mimetypes_row);
+        mimetypes_group.add(&make_default_row);
+
+        let error_label = Label::builder()
+            .wrap(true)
+            .xalign(0.0)
+            .visible(false)
+            .build();
+        error_label.add_css_class("error");
+
+        let page = adw::PreferencesPage::new();
+        page.add(&launcher_group);
+        page.add(&mimetypes_group);
+
+        let save_button = gtk::Button::with_label("Save");
+        save_button.add_css_class("suggested-action");
+
+        let launcher = RefCell::new(launcher);
+        let obj_weak = self.obj().downgrade();
+        let dialog_weak = dialog.downgrade();
+        let error_label_weak = error_label.downgrade();
+        save_button.connect_clicked({
+            let name_row = name_row.clone();
+            move |_| {
+                let (Some(obj), Some(dialog), Some(error_label)) = (
+                    obj_weak.upgrade(),
+                    dialog_weak.upgrade(),
+                    error_label_weak.upgrade(),
+                ) else {
+                    return;
+                };
+                let imp = obj.imp();
+
+                let mut launcher = launcher.borrow_mut();
+                launcher.name = name_row.text().to_string();
+                launcher.exec = exec_row.text().to_string();
+                launcher.icon = icon_row.text().trim().to_string();
+                launcher.terminal = terminal_row.is_active();
+                launcher.mimetypes = mimetypes_row
+                    .text()
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|mimetype| !mimetype.is_empty())
+                    .map(str::to_string)
+                    .collect();
+
+                // Don't take the ID of an installed application, it would be
+                // shadowed
+                let result =
+                    launcher.save(|id| imp.desktop_manager.borrow().get_any_entry(id).is_some());
+                let app_id = match result {
+                    Ok(app_id) => app_id,
+                    Err(e) => {
+                        error_label.set_label(&e.to_string());
+                        error_label.set_visible(true);
+                        return;
+                    }
+                };
+
+                if make_default_row.is_active() {
+                    let description = format!(
+                        "Set {} as default for {}",
+                        launcher.name.trim(),
+                        describe_types(&launcher.mimetypes)
+                    );
+                    imp.stage(&description, |mimetype_manager| {
+                        for mimetype in &launcher.mimetypes {
+                            mimetype_manager.set_default_app(mimetype, &app_id);
+                        }
+                    });
+                }
+
+                imp.reload_desktop_entries();
+                if let Some(row) = imp.app_row(&app_id) {
+                    imp.apps_list_box.select_row(Some(&row));
+                }
+                dialog.close();
+            }
+        });
+
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        content.append(&page);
+        content.append(&error_label);
+        error_label.set_margin_start(12);
+        error_label.set_margin_end(12);
+        error_label.set_margin_bottom(12);
+
+        let header_bar = adw::HeaderBar::new();
+        header_bar.pack_end(&save_button);
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&header_bar);
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+        dialog.set_focus(Some(&name_row));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    // Reads the desktop files again and shows what changed
+    fn reload_desktop_entries(&self) {
+        if let Err(e) = self.desktop_manager.borrow_mut().load_entries() {
+            eprintln!("Failed to load desktop entries: {}", e);
+        }
+        self.app_choices.replace(None);
+        self.populate_filter_buttons();
+        self.populate_apps_list();
+        self.refresh_selected_app();
+    }
+
+    // Watches every mimeapps.list and applications directory, reloading
+    // whatever changed on disk
+    fn setup_file_monitors(&self) {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in self.desktop_manager.borrow().applications_dirs() {
+            collect_dirs(&dir, &mut dirs);
+        }
+
+        // Those in applications directories are covered by watching the
+        // directory
+        let mimeapps_paths: Vec<PathBuf> = self
+            .mimetype_manager
+            .borrow()
+            .as_ref()
+            .map(|manager| manager.mimeapps_paths())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|path| {
+                path.parent()
+                    .is_none_or(|dir| !dirs.iter().any(|d| d == dir))
+            })
+            .collect();
+
+        let mut monitors = Vec::new();
+        for dir in &dirs {
+            match gio::File::for_path(dir)
+                .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            {
+                Ok(monitor) => monitors.push(monitor),
+                Err(e) => eprintln!("Failed to watch {}: {}", dir.display(), e),
+            }
+        }
+        for path in &mimeapps_paths {
+            match gio::File::for_path(path)
+                .monitor_file(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            {
+                Ok(monitor) => monitors.push(monitor),
+                Err(e) => eprintln!("Failed to watch {}: {}", path.display(), e),
+            }
+        }
+
+        for monitor in &monitors {
+            let obj_weak = self.obj().downgrade();
+            monitor.connect_changed(move |_, file, other_file, event| {
+                let Some(obj) = obj_weak.upgrade() else {
+                    return;
+                };
+                if matches!(
+                    event,
+                    gio::FileMonitorEvent::Changed | gio::FileMonitorEvent::AttributeChanged
+                ) {
+                    // Wait for ChangesDoneHint instead
+                    return;
+                }
+                let imp = obj.imp();
+                // Renames report both the old and the new name
+                let paths = std::iter::once(file.path())
+                    .chain(other_file.map(|file| file.path()))
+                    .flatten();
+                for path in paths {
+                    imp.on_file_changed(&path);
+                }
+            });
+        }
+
+        self.file_monitors.replace(monitors);
+    }
+
+    fn on_file_changed(&self, path: &Path) {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Temporary files, like the ones our own atomic writes use
+        if file_name.starts_with('.') {
+            return;
+        }
+
+        if file_name.ends_with("mimeapps.list") {
+            // Our own saves don't need reloading
+            let is_own_write = self
+                .mimetype_manager
+                .borrow()
+                .as_ref()
+                .is_some_and(|manager| {
+                    manager.user_config_path() == path && !manager.has_external_changes()
+                });
+            if is_own_write {
+                return;
+            }
+            self.mimeapps_changed.set(true);
+        } else {
+            self.desktop_entries_changed.set(true);
+        }
+
+        // Installing a package changes many files at once, reload once
+        if let Some(source) = self.reload_source.take() {
+            source.remove();
+        }
+        let obj_weak = self.obj().downgrade();
+        let source = glib::timeout_add_local_once(RELOAD_DELAY, move || {
+            if let Some(obj) = obj_weak.upgrade() {
+                let imp = obj.imp();
+                imp.reload_source.replace(None);
+                imp.reload_changed_files();
+            }
+        });
+        self.reload_source.replace(Some(source));
+    }
+
+    fn reload_changed_files(&self) {
+        if self.mimeapps_changed.replace(false) {
+            let result = match self.mimetype_manager.borrow_mut().as_mut() {
+                Some(mimetype_manager) => mimetype_manager.reload(),
+                None => Ok(()),
+            };
+            if let Err(e) = result {
+                match e.downcast_ref::<ConflictError>() {
+                    Some(conflict) => self.show_conflicts_dialog(conflict, false),
+                    None => eprintln!("Failed to reload mimeapps.list: {}", e),
+                }
+            }
+        }
+
+        if self.desktop_entries_changed.replace(false) {
+            self.reload_desktop_entries();
+            // Directories may have come or gone
+            self.setup_file_monitors();
+        }
+
+        // Staged changes may now be saved already, or conflict
+        self.update_changes_banner();
+        self.refresh_after_reload();
+    }
+
+    // Reports a failed save. When another program changed mimeapps.list in
+    // the meantime, asks whether to apply our changes on top or overwrite it.
+    fn handle_save_error(&self, message: &str, e: &(dyn std::error::Error + 'static)) {
+        if let Some(conflict) = e.downcast_ref::<ConflictError>() {
+            self.show_conflicts_dialog(conflict, true);
+            return;
+        }
+        let Some(conflict) = e.downcast_ref::<ExternalChangeError>() else {
+            eprintln!("{}: {}", message, e);
+            return;
+        };
+
+        let dialog = adw::AlertDialog::new(
+            Some("Configuration Changed on Disk"),
+            Some(&format!(
+                "Another program changed {} since it was loaded. Your changes can be applied on top of it, or replace it entirely.",
+                conflict.path.display()
+            )),
+        );
+        dialog.add_responses(&[
+            ("cancel", "Cancel"),
+            ("merge", "Keep Both"),
+            ("overwrite", "Overwrite"),
+        ]);
+        dialog.set_response_appearance("merge", adw::ResponseAppearance::Suggested);
+        dialog.set_response_appearance("overwrite", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("merge"));
+        dialog.set_close_response("cancel");
+
+        let obj_weak = self.obj().downgrade();
+        dialog.connect_response(None, move |_, response| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+
+            let result = match imp.mimetype_manager.borrow_mut().as_mut() {
+                Some(mimetype_manager) => match response {
+                    "merge" => mimetype_manager
+                        .reload()
+                        .and_then(|()| mimetype_manager.save()),
+                    "overwrite" => mimetype_manager.overwrite_external_changes(),
+                    _ => Ok(()),
+                },
+                None => Ok(()),
+            };
+            // Our changes may be about the same types as theirs
+            if let Err(e) = result {
+                imp.handle_save_error("Failed to save mimeapps.list", &*e);
+            }
+
+            imp.update_changes_banner();
+            imp.refresh_after_reload();
+        });
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    // Asks whose version to keep of the types changed both here and by
+    // another program, then saves if `save` is set
+    fn show_conflicts_dialog(&self, conflict: &ConflictError, save: bool) {
+        let dialog = adw::AlertDialog::new(
+            Some("Conflicting Changes"),
+            Some(&format!(
+                "Another program changed {} for types with unsaved changes here. Choose which version to keep for them, your other changes are kept either way.",
+                conflict.path.display()
+            )),
+        );
+
+        let list_box = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .valign(gtk::Align::Start)
+            .build();
+        list_box.add_css_class("boxed-list");
+        for change in &conflict.conflicts {
+            list_box.append(
+                &adw::ActionRow::builder()
+                    .title(&change.mimetype)
+                    .subtitle(format!(
+                        "{}: {} on disk, {} here",
+                        change.kind,
+                        self.describe_apps(&change.old),
+                        self.describe_apps(&change.new)
+                    ))
+                    .subtitle_lines(0)
+                    .use_markup(false)
+                    .build(),
+            );
+        }
+        dialog.set_extra_child(Some(
+            &gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .max_content_height(240)
+                .propagate_natural_height(true)
+                .child(&list_box)
+                .build(),
+        ));
+
+        dialog.add_responses(&[
+            ("cancel", "Decide Later"),
+            ("theirs", "Use Theirs"),
+            ("mine", "Keep Mine"),
+        ]);
+        dialog.set_response_appearance("mine", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("mine"));
+        dialog.set_close_response("cancel");
+
+        let obj_weak = self.obj().downgrade();
+        dialog.connect_response(None, move |_, response| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return;
+            };
+            let imp = obj.imp();
+            let keep_mine = match response {
+                "mine" => true,
+                "theirs" => false,
+                _ => return,
+            };
+
+            let result =
+                match imp.mimetype_manager.borrow_mut().as_mut() {
+                    Some(mimetype_manager) => mimetype_manager
+                        .resolve_conflicts(keep_mine)
+                        .and_then(|()| {
+                            if save {
+                                mimetype_manager.save()
+                            } else {
+                                Ok(())
+                            }
+                        }),
+                    None => Ok(()),
+                };
+            if let Err(e) = result {
+                imp.handle_save_error("Failed to save mimeapps.list", &*e);
+            }
+
+            imp.update_changes_banner();
+            imp.refresh_after_reload();
+        });
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    pub fn show_diagnostics_dialog(&self) {
+        let desktop_manager = self.desktop_manager.borrow();
+        let shadowed = desktop_manager.get_shadowed_entries();
+
+        let dialog = adw::Dialog::builder()
+            .title("Diagnostics")
+            .content_width(560)
+            .content_height(480)
+            .build();
+
+        let content: gtk::Widget = if shadowed.is_empty() {
+            adw::StatusPage::builder()
+                .icon_name("emblem-ok-symbolic")
+                .title("No Shadowed Desktop Files")
+                .description("Every desktop file has a unique ID")
+                .build()
+                .upcast()
+        } else {
+            let page = adw::PreferencesPage::new();
+            let group = adw::PreferencesGroup::builder()
+                .title("Shadowed Desktop Files")
+                .description(
+                    "Only the first file with a given ID is used: the user's \
+                     applications directory comes first, then each of \
+                     $XDG_DATA_DIRS in order",
+                )
+                .build();
+            page.add(&group);
+
+            for entry in shadowed {
+                let row = adw::ActionRow::builder()
+                    .title(&entry.id)
+                    .subtitle(format!(
+                        "{}\nShadowed by {}",
+                        entry.path.display(),
+                        entry.shadowed_by.display()
+                    ))
+                    .use_markup(false)
+                    .build();
+                group.add(&row);
+            }
+
+            page.upcast()
+        };
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&content));
+        dialog.set_child(Some(&toolbar_view));
+
+        dialog.present(Some(&*self.obj()));
+    }
+
+    // Show the selected app again, after its associations changed underneath
+    fn refresh_selected_app(&self) {
+        let selected_app_id = self.selected_app_id.borrow().clone();
+        if let Some(app_id) = selected_app_id {
+            self.populate_app_mimetypes(&app_id);
+        }
+    }
+
+    fn selected_desktop_file(&self) -> Option<String> {
+        self.selected_app_id.borrow().clone()
+    }
+
+    pub fn setup_filtering(&self) {
+        let obj_weak = self.obj().downgrade();
+
+        // Set up filter function for the apps list
+        let apps_filter = gtk::CustomFilter::new(move |item| {
+            let Some(app) = item.downcast_ref::<AppObject>() else {
+                return false;
+            };
+            let entry = app.entry();
+
+            let Some(obj) = obj_weak.upgrade() else {
+                return true;
+            };
+            let imp = obj.imp();
+
+            imp.is_app_shown(entry)
+                && (imp.search_query.borrow().is_empty()
+                    || imp.search_scores.borrow().contains_key(&entry.id))
+        });
+
+        // Best matches first, apps are in alphabetical order otherwise
+        let obj_weak = self.obj().downgrade();
+        let apps_sorter = gtk::CustomSorter::new(move |first, second| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return gtk::Ordering::Equal;
+            };
+            let scores = obj.imp().search_scores.borrow();
+            let score = |item: &glib::Object| {
+                item.downcast_ref::<AppObject>()
+                    .and_then(|app| scores.get(&app.entry().id).copied())
+                    .unwrap_or_default()
+            };
+            score(second).cmp(&score(first)).into()
+        });
+
+        let apps_sort_model =
+            gtk::SortListModel::new(Some(self.apps_model().clone()), Some(apps_sorter));
+        let apps_filter_model =
+            gtk::FilterListModel::new(Some(apps_sort_model), Some(apps_filter.clone()));
+        let obj_weak = self.obj().downgrade();
+        self.apps_list_box
+            .bind_model(Some(&apps_filter_model), move |item| {
+                let app = item
+                    .downcast_ref::<AppObject>()
+                    .expect("The apps list only holds AppObjects");
+                let entry = app.entry();
+
+                // Characters matching the search are in bold
+                let obj = obj_weak.upgrade();
+                let highlight = |text: &str| match &obj {
+                    Some(obj) => highlight_matches(
+                        text,
+                        &obj.imp().search_query.borrow().matched_indices(text),
+                    ),
+                    None => glib::markup_escape_text(text).to_string(),
+                };
+
+                let row = adw::ActionRow::builder()
+                    .title(highlight(&entry.name))
+                    .build();
+                // What kind of application it is, e.g. "Web Browser"
+                if let Some(description) = entry.generic_name.as_ref().or(entry.comment.as_ref()) {
+                    row.set_subtitle(&highlight(description));
+                    row.set_subtitle_lines(1);
+                }
+                row.set_tooltip_text(entry.comment.as_deref());
+
+                let icon = app_icon(entry.icon.as_deref());
+                icon.set_icon_size(gtk::IconSize::Large);
+                row.add_prefix(&icon);
+
+                // Say why apps that are normally left out are hidden
+                for reason in &entry.hidden_reasons {
+                    row.add_suffix(&badge(&reason.to_string()));
+                }
+
+                // Declared types and those the user associated the app with
+                let mimetype_count = obj
+                    .as_ref()
+                    .map(|obj| obj.imp().app_mimetypes(entry).len())
+                    .unwrap_or(entry.mimetypes.len());
+                if mimetype_count > 0 {
+                    let count = badge(&format!(
+                        "{} {}",
+                        mimetype_count,
+                        if mimetype_count == 1 { "type" } else { "types" }
+                    ));
+                    count.add_css_class("numeric");
+                    row.add_suffix(&count);
+                }
+
+                let source = badge(&entry.source().to_string());
+                source.set_tooltip_text(Some(&entry.path.display().to_string()));
+                row.add_suffix(&source);
+
+                row.upcast()
+            });
+
+        let _ = self.apps_filter.set(apps_filter);
+        let _ = self.apps_filter_model.set(apps_filter_model);
+
+        // The selected app's mimetypes follow the filter chips too
+        let obj_weak = self.obj().downgrade();
+        self.app_mime_types_list_box.set_filter_func(move |row| {
+            let Some(obj) = obj_weak.upgrade() else {
+                return true;
+            };
+            let imp = obj.imp();
+
+            usize::try_from(row.index())
+                .ok()
+                .and_then(|index| imp.displayed_mimetypes.borrow().get(index).cloned())
+                .is_none_or(|mimetype| imp.is_type_shown(&mimetype))
+        });
+    }
+
+    pub fn setup_app_selection(&self) {
+        let obj = self.obj();
+        let obj_weak = obj.downgrade();
+
+        self.apps_list_box.connect_row_selected(move |_, row| {
+            if let Some(obj) = obj_weak.upgrade() {
+                let imp = obj.imp();
+                if let Some(row) = row {
+                    let app = imp
+                        .apps_filter_model
+                        .get()
+                        .and_then(|model| model.item(u32::try_from(row.index()).ok()?))
+                        .and_downcast::<AppObject>();
+                    if let Some(app) = app {
+                        let app_id = app.entry().id.clone();
+
+                        // Reselected after refiltering, nothing changed
+                        if imp.selected_app_id.borrow().as_ref() == Some(&app_id) {
+                            return;
+                        }
+
+                        imp.selected_app_id.replace(Some(app_id.clone()));
+                        obj.action_set_enabled("win.add-mimetype", true);
+                        obj.action_set_enabled(
+                            "win.edit-launcher",
+                            launcher::is_user_launcher(&app.entry().path),
+                        );
+                        imp.populate_app_mimetypes(&app_id);
+                    }
+                } else if !imp.refiltering_apps.get() {
+                    // No row selected - clear mimetypes and show no_app_selected_page
+                    imp.selected_app_id.replace(None);
+                    obj.action_set_enabled("win.add-mimetype", false);
+                    obj.action_set_enabled("win.edit-launcher", false);
+                    while let Some(child) = imp.app_mime_types_list_box.first_child() {
+                        imp.app_mime_types_list_box.remove(&child);
+                    }
+                    while let Some(child) = imp.active_app_mime_types_list_box.first_child() {
+                        imp.active_app_mime_types_list_box.remove(&child);
+                    }
+                    imp.mime_types_stack
+                        .set_visible_child_name("no_app_selected_page");
+                }
+            }
+        });
+    }
+}
+
+#[gtk::template_callbacks]
+impl Window {
+    #[template_callback]
+    fn update_apps_list(&self) {
+        self.search_query
+            .replace(SearchQuery::parse(&self.filter_entry.text()));
+        // Rows are recreated, to rank and highlight them again
+        self.populate_apps_list();
+    }
+
+    #[template_callback]
+    fn on_apply_clicked(&self) {
+        // Get the selected app's desktop file ID
+        let selected_app_id = match self.selected_app_id.borrow().as_ref() {
+            Some(app_id) => app_id.clone(),
+            None => {
+                eprintln!("No app selected");
+                return;
+            }
+        };
+
+        // Get the desktop file ID and mimetypes - clone them to avoid borrow issues
+        let (desktop_file_name, app_mimetypes) = {
+            let desktop_manager = self.desktop_manager.borrow();
+
+            let app_entry = match desktop_manager.get_any_entry(&selected_app_id) {
+                Some(entry) => entry,
+                None => {
+                    eprintln!("Could not find app entry for {}", selected_app_id);
+                    return;
+                }
+            };
+
+            (app_entry.id.clone(), self.app_mimetypes(app_entry))
+        }; // desktop_manager borrow ends here
+
+        // Get all selected mimetypes from the list box
+        let selected_mimetypes: Vec<String> = self
+            .app_mime_types_list_box
+            .selected_rows()
+            .iter()
+            .filter(|row| row.is_child_visible())
+            .filter_map(|row| {
+                let index = usize::try_from(row.index()).ok()?;
+                self.displayed_mimetypes.borrow().get(index).cloned()
+            })
+            .collect();
+
+        if selected_mimetypes.is_empty() {
+            eprintln!("No mimetypes selected");
+            return;
+        }
+
+        // Stage the changes, they're written once saved
+        let description = format!(
+            "Set {} as default for {}",
+            self.app_name(&desktop_file_name),
+            describe_types(&selected_mimetypes)
+        );
+        self.stage(&description, |mimetype_manager| {
+            for mimetype in &selected_mimetypes {
+                mimetype_manager.set_default_app(mimetype, &desktop_file_name);
+            }
+        });
 
         // Refresh the active mimetypes display
         self.populate_active_mimetypes(&app_mimetypes);
     }
 }
+
+// The type itself when there's only one
+fn describe_types(mimetypes: &[String]) -> String {
+    match mimetypes {
+        [mimetype] => mimetype.clone(),
+        _ => format!("{} types", mimetypes.len()),
+    }
+}
+
+// `dir`, even if it doesn't exist yet, and every directory below it
+fn collect_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) {
+    dirs.push(dir.to_path_buf());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            collect_dirs(&entry.path(), dirs);
+        }
+    }
+}
+
+// The mimetype itself, plus what the database knows about how it relates to
+// other types
+fn describe_mimetype(mime_database: &MimeDatabase, mimetype: &str) -> String {
+    let canonical = mime_database.canonical(mimetype);
+    if canonical != mimetype {
+        return format!("{} (alias of {})", mimetype, canonical);
+    }
+
+    let parents = mime_database.parents(mimetype);
+    if parents.is_empty() {
+        mimetype.to_string()
+    } else {
+        format!("{} (subclass of {})", mimetype, parents.join(", "))
+    }
+}
+
+// `text` as Pango markup, with the characters at `indices` in bold
+fn highlight_matches(text: &str, indices: &BTreeSet<usize>) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(index, character)| {
+            let escaped = glib::markup_escape_text(&character.to_string());
+            if indices.contains(&index) {
+                format!("<b>{}</b>", escaped)
+            } else {
+                escaped.to_string()
+            }
+        })
+        .collect()
+}
+
+// Small dimmed text next to a row's title
+fn badge(text: &str) -> Label {
+    let badge = Label::new(Some(text));
+    badge.add_css_class("caption");
+    badge.add_css_class("dim-label");
+    badge
+}
+
+// Desktop files name an icon from the theme, or give a path to one
+fn app_icon(icon: Option<&str>) -> gtk::Image {
+    match icon {
+        Some(icon) if Path::new(icon).is_absolute() => gtk::Image::from_file(icon),
+        Some(icon) => gtk::Image::from_icon_name(icon),
+        None => gtk::Image::from_icon_name("application-x-executable"),
+    }
+}
+
+// A boolean window action saved in the settings, refreshing the apps list
+fn toggle_action(
+    name: &str,
+    active: bool,
+    apply: fn(&Window, bool),
+) -> gio::ActionEntry<super::Window> {
+    gio::ActionEntry::builder(name)
+        .state(active.to_variant())
+        .activate(move |window: &super::Window, action, _| {
+            let active = !action
+                .state()
+                .and_then(|state| state.get())
+                .unwrap_or(false);
+            action.set_state(&active.to_variant());
+
+            let imp = window.imp();
+            apply(imp, active);
+            imp.populate_apps_list();
+        })
+        .build()
+}
@@ -2,7 +2,9 @@ mod imp;
 
 use adw::Application;
 use glib::Object;
+use gtk::subclass::prelude::*;
 use gtk::{gio, glib};
+use std::path::Path;
 
 glib::wrapper! {
     pub struct Window(ObjectSubclass<imp::Window>)
@@ -16,4 +18,8 @@ impl Window {
         // Create new window
         Object::builder().property("application", app).build()
     }
+
+    pub fn show_file_handler(&self, path: &Path) {
+        self.imp().show_file_handler_dialog(path);
+    }
 }
@@ -0,0 +1,64 @@
+use std::env;
+use std::path::PathBuf;
+
+// Paths in XDG variables must be absolute, relative ones are ignored per spec
+fn absolute_var(name: &str) -> Option<PathBuf> {
+    env::var_os(name)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+fn absolute_list_var(name: &str, fallback: &[&str]) -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = env::var(name)
+        .unwrap_or_default()
+        .split(':')
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .collect();
+
+    if dirs.is_empty() {
+        fallback.iter().map(PathBuf::from).collect()
+    } else {
+        dirs
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    absolute_var("HOME")
+}
+
+/// `$XDG_CONFIG_HOME`, defaulting to `~/.config`.
+pub fn config_home() -> Option<PathBuf> {
+    absolute_var("XDG_CONFIG_HOME").or_else(|| Some(home_dir()?.join(".config")))
+}
+
+/// `$XDG_CONFIG_DIRS` in order of preference, defaulting to `/etc/xdg`.
+pub fn config_dirs() -> Vec<PathBuf> {
+    absolute_list_var("XDG_CONFIG_DIRS", &["/etc/xdg"])
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share`.
+pub fn data_home() -> Option<PathBuf> {
+    absolute_var("XDG_DATA_HOME").or_else(|| Some(home_dir()?.join(".local").join("share")))
+}
+
+/// `$XDG_DATA_DIRS` in order of preference, defaulting to
+/// `/usr/local/share:/usr/share`.
+pub fn data_dirs() -> Vec<PathBuf> {
+    absolute_list_var("XDG_DATA_DIRS", &["/usr/local/share", "/usr/share"])
+}
+
+/// `$XDG_STATE_HOME`, defaulting to `~/.local/state`.
+pub fn state_home() -> Option<PathBuf> {
+    absolute_var("XDG_STATE_HOME").or_else(|| Some(home_dir()?.join(".local").join("state")))
+}
+
+/// Lowercased names from `$XDG_CURRENT_DESKTOP`, most specific first.
+pub fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}